@@ -38,6 +38,7 @@ export async function POST(request: NextRequest) {
         })),
         compliance_score: result.compliance_score,
         redacted_text: result.redacted_text,
+        redaction_map: result.redaction_map,
         recommendations: result.recommendations,
         processing_time: result.processing_time
       }
@@ -8,11 +8,20 @@ export interface PiiResult {
   }
 }
 
+export interface RedactionSpan {
+  pii_type: string
+  original_start: number
+  original_end: number
+  redacted_start: number
+  redacted_end: number
+}
+
 export interface ScanResponse {
   text: string
   pii_detected: PiiResult[]
   compliance_score: number
   redacted_text: string
+  redaction_map?: RedactionSpan[]
   recommendations: string[]
   processing_time: number
 }
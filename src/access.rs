@@ -0,0 +1,38 @@
+use sha2::{Digest, Sha256};
+
+/// Shared secret an HTTP caller presents as `Authorization: Bearer <key>`.
+/// Only its digest is kept, and presented keys are compared in constant time.
+#[derive(Clone)]
+pub struct AccessKey {
+    digest: [u8; 32],
+}
+
+impl AccessKey {
+    pub fn new(key: &str) -> Self {
+        Self { digest: Sha256::digest(key.as_bytes()).into() }
+    }
+
+    /// Read from the environment variable `name`, `None` when it is unset
+    pub fn from_env(name: &str) -> Option<Self> {
+        std::env::var(name).ok().filter(|k| !k.is_empty()).map(|k| Self::new(&k))
+    }
+
+    pub fn verify(&self, presented: &str) -> bool {
+        let presented: [u8; 32] = Sha256::digest(presented.as_bytes()).into();
+        // Compare digests without short-circuiting
+        presented.iter().zip(self.digest).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_accepts_only_the_key() {
+        let key = AccessKey::new("s3cret");
+        assert!(key.verify("s3cret"));
+        assert!(!key.verify("s3cre"));
+        assert!(!key.verify(""));
+    }
+}
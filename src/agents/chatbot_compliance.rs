@@ -1,141 +1,537 @@
+use crate::audit::AuditLog;
+use crate::consent::{ConsentRegistry, DEFAULT_CONSENT_PURPOSE};
+use crate::llm::{self, LlmBackend, PromptTemplates};
+use crate::models::{resolve_spans, PiiClassifier};
+use crate::policy::{CompliancePolicy, GuardrailAction, PolicyAction};
+use crate::redaction::{self, RedactionContext, RedactionOutput};
 use crate::types::{
     AgentContext, AgentMessage, ChatMessage, ChatSession, ComplianceResult, ComplianceViolation,
-    MessageType, PiiDetection, RiskLevel, Severity, ViolationType,
+    CrossMessageDetection, GuardrailDecision, GuardrailResult, MessageType, PiiDetection, RiskLevel, Severity, ViolationType,
 };
+use crate::hashing::KeyedHasher;
+use crate::pseudonym::Pseudonymizer;
+use crate::synthetic::Synthesizer;
+use crate::session_store::{InMemorySessionStore, SessionStore};
+use crate::vault::TokenVault;
+use super::{Agent, LlmExtractorAgent};
 use anyhow::Result;
 use async_trait::async_trait;
+use serde::Serialize;
 use serde_json::json;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+/// What one retention sweep changed
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RetentionReport {
+    pub sessions_checked: usize,
+    /// Messages whose raw content was replaced by the redacted text
+    pub raw_purged: usize,
+    pub messages_removed: usize,
+    /// Detections in older violations that still held a raw value
+    pub values_scrubbed: usize,
+    /// `DataRetention` violations raised for overdue data
+    pub violations: usize,
+}
+
 #[derive(Clone)]
 pub struct ChatbotComplianceAgent {
     agent_id: String,
-    active_sessions: Arc<Mutex<HashMap<String, ChatSession>>>,
-    compliance_rules: HashMap<String, Severity>,
+    sessions: Arc<dyn SessionStore>,
+    /// Serializes load-modify-save of sessions
+    session_lock: Arc<Mutex<()>>,
+    policy: Arc<CompliancePolicy>,
+    /// Stricter policy for bot replies, derived from `policy`
+    outbound_policy: Arc<CompliancePolicy>,
+    classifier: Arc<Mutex<PiiClassifier>>,
+    extractor: Option<LlmExtractorAgent>,
+    vault: Option<Arc<TokenVault>>,
+    pseudonymizer: Option<Arc<Pseudonymizer>>,
+    hasher: Option<Arc<KeyedHasher>>,
+    synthesizer: Option<Arc<Synthesizer>>,
+    backend: Option<Arc<dyn LlmBackend>>,
+    prompts: PromptTemplates,
+    consent: Option<Arc<ConsentRegistry>>,
+    consent_purpose: String,
+    audit: Option<Arc<AuditLog>>,
 }
 
 impl ChatbotComplianceAgent {
     pub fn new() -> Self {
-        let mut compliance_rules = HashMap::new();
-        compliance_rules.insert("SSN".to_string(), Severity::Critical);
-        compliance_rules.insert("CreditCard".to_string(), Severity::High);
-        compliance_rules.insert("Email".to_string(), Severity::Medium);
-        compliance_rules.insert("Phone".to_string(), Severity::Medium);
-        compliance_rules.insert("Address".to_string(), Severity::Low);
-        
+        let policy = CompliancePolicy::default();
         Self {
             agent_id: "chatbot-compliance-001".to_string(),
-            active_sessions: Arc::new(Mutex::new(HashMap::new())),
-            compliance_rules,
+            sessions: Arc::new(InMemorySessionStore::default()),
+            session_lock: Arc::new(Mutex::new(())),
+            outbound_policy: Arc::new(policy.outbound()),
+            policy: Arc::new(policy),
+            classifier: Arc::new(Mutex::new(PiiClassifier::new())),
+            extractor: None,
+            vault: None,
+            pseudonymizer: None,
+            hasher: None,
+            synthesizer: None,
+            backend: None,
+            prompts: PromptTemplates::default(),
+            consent: None,
+            consent_purpose: DEFAULT_CONSENT_PURPOSE.to_string(),
+            audit: None,
         }
     }
     
+    pub fn with_classifier(mut self, classifier: PiiClassifier) -> Self {
+        self.classifier = Arc::new(Mutex::new(classifier));
+        self
+    }
+    
+    /// Adds LLM extraction of names, addresses and the like to each message
+    pub fn with_extractor(mut self, extractor: Option<LlmExtractorAgent>) -> Self {
+        self.extractor = extractor;
+        self
+    }
+    
+    /// Checks PII in user messages against `registry` for the session's
+    /// purpose, or `purpose` when the session declares none: consented
+    /// categories are handled per policy without a violation, the rest raise
+    /// `ConsentMissing`. Without a registry all PII counts as `PiiExposure`.
+    pub fn with_consent(mut self, registry: Option<Arc<ConsentRegistry>>, purpose: &str) -> Self {
+        self.consent = registry;
+        self.consent_purpose = purpose.to_string();
+        self
+    }
+    
+    /// Records every user message and bot reply decision in `audit`
+    pub fn with_audit(mut self, audit: Option<Arc<AuditLog>>) -> Self {
+        self.audit = audit;
+        self
+    }
+    
+    /// Declares what the session is for. `purpose` must be one of the
+    /// policy's `purposes`; PII outside its allowlist raises
+    /// `DataMinimization`, and consent is checked for this purpose.
+    pub async fn set_session_purpose(&self, session_id: &str, user_id: &str, purpose: &str) -> Result<()> {
+        if self.policy.purpose(purpose).is_none() {
+            let mut known: Vec<&String> = self.policy.purposes.keys().collect();
+            known.sort();
+            anyhow::bail!("unknown session purpose {:?}; the policy defines {:?}", purpose, known);
+        }
+        
+        let _guard = self.session_lock.lock().await;
+        let mut session = self.sessions.load(session_id)?.unwrap_or_else(|| ChatSession {
+            session_id: session_id.to_string(),
+            user_id: user_id.to_string(),
+            messages: vec![],
+            compliance_violations: vec![],
+            risk_level: RiskLevel::Safe,
+            redacted_content: HashMap::new(),
+            purpose: None,
+            last_activity: chrono::Utc::now(),
+        });
+        session.purpose = Some(purpose.to_string());
+        session.last_activity = chrono::Utc::now();
+        self.sessions.save(&session)
+    }
+    
+    /// Keeps sessions in `store`, e.g. a `SqliteSessionStore` so session risk
+    /// survives restarts; the default is an in-memory store
+    pub fn with_session_store(mut self, store: Arc<dyn SessionStore>) -> Self {
+        self.sessions = store;
+        self
+    }
+    
+    /// A stored session, rehydrated after a restart when the store is persistent
+    pub fn session(&self, session_id: &str) -> Result<Option<ChatSession>> {
+        self.sessions.load(session_id)
+    }
+    
+    pub fn session_risk_level(&self, session_id: &str) -> Result<Option<RiskLevel>> {
+        self.sessions.risk_level(session_id)
+    }
+    
+    /// Drops sessions idle for longer than the store's TTL
+    pub fn evict_expired_sessions(&self) -> Result<usize> {
+        self.sessions.evict_expired()
+    }
+    
+    /// Enforces `[retention]` on every stored session: raw content past its
+    /// window is replaced by the redacted text, and messages past the
+    /// redacted window are removed. Data found more than one sweep interval
+    /// past its window is recorded as a `DataRetention` violation. Violations
+    /// recorded with raw values, before they were scrubbed on write, are
+    /// scrubbed too.
+    pub async fn sweep_retention(&self) -> Result<RetentionReport> {
+        let retention = &self.policy.retention;
+        let now = chrono::Utc::now();
+        let raw_cutoff = now - chrono::Duration::hours(retention.raw_content_hours);
+        let redacted_cutoff = now - chrono::Duration::days(retention.redacted_content_days);
+        let grace = chrono::Duration::seconds(retention.sweep_interval_secs as i64);
+        
+        let mut report = RetentionReport::default();
+        for session_id in self.sessions.session_ids()? {
+            let _guard = self.session_lock.lock().await;
+            let Some(mut session) = self.sessions.load(&session_id)? else {
+                continue;
+            };
+            report.sessions_checked += 1;
+            
+            let mut overdue: Vec<String> = vec![];
+            let mut changed = false;
+            
+            session.messages.retain(|message| {
+                if message.timestamp > redacted_cutoff {
+                    return true;
+                }
+                if message.timestamp <= redacted_cutoff - grace {
+                    overdue.push(message.message_id.clone());
+                }
+                session.redacted_content.remove(&message.message_id);
+                report.messages_removed += 1;
+                changed = true;
+                false
+            });
+            
+            for message in session.messages.iter_mut().filter(|m| m.timestamp <= raw_cutoff) {
+                let Some(redacted) = session.redacted_content.remove(&message.message_id) else {
+                    continue;
+                };
+                if message.timestamp <= raw_cutoff - grace {
+                    overdue.push(message.message_id.clone());
+                }
+                message.content = redacted;
+                report.raw_purged += 1;
+                changed = true;
+            }
+            
+            for detection in session.compliance_violations.iter_mut().flat_map(|v| v.pii_detected.iter_mut()) {
+                let replacement = self.policy.replacement(&detection.pii_type);
+                if detection.value != replacement {
+                    detection.value = replacement;
+                    report.values_scrubbed += 1;
+                    changed = true;
+                }
+            }
+            
+            if !overdue.is_empty() {
+                session.compliance_violations.push(ComplianceViolation {
+                    violation_type: ViolationType::DataRetention,
+                    severity: retention.severity.clone(),
+                    message: format!(
+                        "{} message(s) stored past the retention window ({}h raw, {}d redacted)",
+                        overdue.len(), retention.raw_content_hours, retention.redacted_content_days
+                    ),
+                    timestamp: now,
+                    pii_detected: vec![],
+                    message_ids: overdue,
+                });
+                session.risk_level = self.calculate_session_risk_level(&session);
+                report.violations += 1;
+            }
+            
+            if changed {
+                self.sessions.save(&session)?;
+            }
+        }
+        
+        Ok(report)
+    }
+    
+    /// Runs `sweep_retention` and session eviction every
+    /// `retention.sweep_interval_secs` until the returned task is aborted
+    pub fn spawn_retention_sweeper(&self) -> tokio::task::JoinHandle<()> {
+        let agent = self.clone();
+        let period = std::time::Duration::from_secs(self.policy.retention.sweep_interval_secs);
+        
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(period);
+            loop {
+                ticker.tick().await;
+                match agent.sweep_retention().await {
+                    Ok(report) if report.raw_purged + report.messages_removed + report.values_scrubbed > 0 => {
+                        tracing::info!("Retention sweep: {:?}", report);
+                    }
+                    Ok(_) => {}
+                    Err(e) => tracing::error!("Retention sweep failed: {:#}", e),
+                }
+                if let Err(e) = agent.evict_expired_sessions() {
+                    tracing::error!("Session eviction failed: {:#}", e);
+                }
+            }
+        })
+    }
+    
+    pub fn with_policy(mut self, policy: Arc<CompliancePolicy>) -> Self {
+        self.outbound_policy = Arc::new(policy.outbound());
+        self.policy = policy;
+        self
+    }
+    
+    /// Vault for the `tokenize` action; without one tokenized values are redacted
+    pub fn with_vault(mut self, vault: Option<Arc<TokenVault>>) -> Self {
+        self.vault = vault;
+        self
+    }
+    
+    /// Keys the `pseudonymize` action; without one pseudonymized values are redacted
+    pub fn with_pseudonymizer(mut self, pseudonymizer: Option<Arc<Pseudonymizer>>) -> Self {
+        self.pseudonymizer = pseudonymizer;
+        self
+    }
+    
+    /// Keys the `hash` action; without one hashed values are redacted
+    pub fn with_hasher(mut self, hasher: Option<Arc<KeyedHasher>>) -> Self {
+        self.hasher = hasher;
+        self
+    }
+    
+    /// Keys the `synthesize` action; without one synthesized values are redacted
+    pub fn with_synthesizer(mut self, synthesizer: Option<Arc<Synthesizer>>) -> Self {
+        self.synthesizer = synthesizer;
+        self
+    }
+    
+    /// Rewrites bot replies for the `regenerate` guardrail action; without a
+    /// backend those replies are redacted instead
+    pub fn with_backend(mut self, backend: Option<Arc<dyn LlmBackend>>, prompts: PromptTemplates) -> Self {
+        self.backend = backend;
+        self.prompts = prompts;
+        self
+    }
+    
     pub async fn process_chat_message(&self, message: ChatMessage) -> Result<ComplianceResult> {
         // Check for PII in the message
-        let pii_detections = self.detect_pii_in_message(&message.content).await?;
+        let mut pii_detections = self.detect_pii_in_message(&message.content, &self.policy).await?;
         
-        // Create compliance result
-        let redacted_content = self.redact_pii(&message.content, &pii_detections);
-        let compliance_score = self.calculate_compliance_score(&pii_detections);
-        let recommendations = self.generate_recommendations(&pii_detections);
+        // PII split across this and earlier messages is redacted in each of them
+        let mut cross_message_pii = vec![];
+        let mut earlier_fragments: Vec<(String, PiiDetection)> = vec![];
+        for (detection, fragments) in self.detect_cross_message_pii(&message).await {
+            for (message_id, fragment) in fragments {
+                if message_id == message.message_id {
+                    pii_detections.push(fragment);
+                } else {
+                    earlier_fragments.push((message_id, fragment));
+                }
+            }
+            cross_message_pii.push(detection);
+        }
+        if !cross_message_pii.is_empty() {
+            pii_detections = resolve_spans(&message.content, pii_detections);
+        }
         
-        let compliance_result = ComplianceResult {
-            original_text: message.content.clone(),
-            redacted_text: redacted_content,
-            detected_pii: pii_detections.clone(),
-            compliance_score,
-            recommendations,
-        };
+        // Create compliance result
+        let mut compliance_result = self.build_compliance_result(&message, pii_detections, &self.policy);
+        compliance_result.cross_message_pii = cross_message_pii;
         
         // Update session with compliance info
-        self.update_session_compliance(&message, &compliance_result).await?;
+        let violations = self.update_session_compliance(
+            &message, &compliance_result, &self.policy, ViolationType::PiiExposure, &earlier_fragments,
+        ).await?;
+        
+        let decision = AuditLog::redaction_decision(&self.policy, &compliance_result.detected_pii);
+        self.audit_decision(&message, &compliance_result, &self.policy, decision, violations)?;
         
         Ok(compliance_result)
     }
     
-    async fn detect_pii_in_message(&self, content: &str) -> Result<Vec<PiiDetection>> {
-        // Use the existing PII detection logic
-        // This would integrate with your PiiScannerAgent
-        let mut detections = vec![];
-        
-        // Simple regex-based detection for demo
-        let email_regex = regex::Regex::new(r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Z|a-z]{2,}\b")?;
-        let phone_regex = regex::Regex::new(r"\b\d{3}[-.]?\d{3}[-.]?\d{4}\b")?;
-        let ssn_regex = regex::Regex::new(r"\b\d{3}-\d{2}-\d{4}\b")?;
-        let cc_regex = regex::Regex::new(r"\b\d{4}[- ]?\d{4}[- ]?\d{4}[- ]?\d{4}\b")?;
-        
-        for mat in email_regex.find_iter(content) {
-            detections.push(PiiDetection {
-                pii_type: crate::types::PiiType::Email,
-                confidence: 0.9,
-                start_pos: mat.start(),
-                end_pos: mat.end(),
-                value: mat.as_str().to_string(),
-            });
+    /// Rescans the session's recent user messages joined with `message` and
+    /// returns the detections that span more than one message and reach into
+    /// this one, each with the part that falls inside every message it
+    /// touches, by message ID. Only the classifier runs on the joined text.
+    async fn detect_cross_message_pii(&self, message: &ChatMessage) -> Vec<(CrossMessageDetection, Vec<(String, PiiDetection)>)> {
+        let window = &self.policy.chat_window;
+        if window.messages < 2 {
+            return vec![];
         }
         
-        for mat in phone_regex.find_iter(content) {
-            detections.push(PiiDetection {
-                pii_type: crate::types::PiiType::PhoneNumber,
-                confidence: 0.9,
-                start_pos: mat.start(),
-                end_pos: mat.end(),
-                value: mat.as_str().to_string(),
-            });
+        let stored = match self.sessions.load(&message.session_id) {
+            Ok(stored) => stored,
+            Err(e) => {
+                tracing::error!("Failed to load session {}, scanning message alone: {:#}", message.session_id, e);
+                None
+            }
+        };
+        let mut segments: Vec<(String, String)> = stored
+            .map(|session| session.messages.iter()
+                .filter(|m| m.is_user_message && m.message_id != message.message_id)
+                .rev()
+                .take(window.messages - 1)
+                .map(|m| (m.message_id.clone(), m.content.clone()))
+                .collect())
+            .unwrap_or_default();
+        segments.reverse();
+        segments.push((message.message_id.clone(), message.content.clone()));
+        
+        // The newest message always stays, however long it is
+        while segments.len() > 1 && segments.iter().map(|(_, text)| text.len() + 1).sum::<usize>() > window.max_chars {
+            segments.remove(0);
+        }
+        if segments.len() < 2 {
+            return vec![];
         }
         
-        for mat in ssn_regex.find_iter(content) {
-            detections.push(PiiDetection {
-                pii_type: crate::types::PiiType::SocialSecurityNumber,
-                confidence: 0.95,
-                start_pos: mat.start(),
-                end_pos: mat.end(),
-                value: mat.as_str().to_string(),
-            });
+        let mut text = String::new();
+        let mut ranges = Vec::with_capacity(segments.len());
+        for (message_id, content) in &segments {
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            ranges.push((message_id.as_str(), content.as_str(), text.len(), text.len() + content.len()));
+            text.push_str(content);
         }
+        let detections = self.policy.filter(self.classifier.lock().await.detect_pii(&text));
+        
+        let (_, _, current_start, current_end) = ranges[ranges.len() - 1];
+        detections.into_iter()
+            .filter(|d| d.start_pos < current_end && current_start < d.end_pos)
+            .filter_map(|detection| {
+                let fragments: Vec<(String, PiiDetection)> = ranges.iter()
+                    .filter(|(_, _, start, end)| detection.start_pos < *end && *start < detection.end_pos)
+                    .map(|(message_id, content, start, end)| {
+                        let start_pos = detection.start_pos.max(*start) - start;
+                        let end_pos = detection.end_pos.min(*end) - start;
+                        let fragment = PiiDetection {
+                            start_pos,
+                            end_pos,
+                            value: content[start_pos..end_pos].to_string(),
+                            ..detection.clone()
+                        };
+                        (message_id.to_string(), fragment)
+                    })
+                    .collect();
+                if fragments.len() < 2 {
+                    return None;
+                }
+                
+                let message_ids = fragments.iter().map(|(message_id, _)| message_id.clone()).collect();
+                Some((CrossMessageDetection { detection, message_ids }, fragments))
+            })
+            .collect()
+    }
+    
+    /// Outbound guardrail: scans a bot reply under the outbound policy and
+    /// decides what the user receives. PII in the reply is recorded as a
+    /// `BotDisclosure` violation whatever the guardrail action.
+    pub async fn process_bot_message(&self, message: ChatMessage) -> Result<GuardrailResult> {
+        let policy = &self.outbound_policy;
+        let pii_detections = self.detect_pii_in_message(&message.content, policy).await?;
+        let compliance_result = self.build_compliance_result(&message, pii_detections, policy);
+        
+        let violations = self.update_session_compliance(&message, &compliance_result, policy, ViolationType::BotDisclosure, &[]).await?;
         
-        for mat in cc_regex.find_iter(content) {
-            detections.push(PiiDetection {
-                pii_type: crate::types::PiiType::CreditCardNumber,
-                confidence: 0.9,
-                start_pos: mat.start(),
-                end_pos: mat.end(),
-                value: mat.as_str().to_string(),
+        if compliance_result.detected_pii.is_empty() {
+            self.audit_decision(&message, &compliance_result, policy, GuardrailDecision::Passed, violations)?;
+            return Ok(GuardrailResult {
+                decision: GuardrailDecision::Passed,
+                delivered_text: message.content.clone(),
+                compliance_result,
+                regenerate_attempts: 0,
             });
         }
         
-        Ok(detections)
+        let guardrail = &policy.guardrail;
+        let (decision, delivered_text, regenerate_attempts) = match guardrail.action {
+            GuardrailAction::Block => (GuardrailDecision::Blocked, guardrail.block_message.clone(), 0),
+            GuardrailAction::Redact => (GuardrailDecision::Redacted, compliance_result.redacted_text.clone(), 0),
+            GuardrailAction::Regenerate => match self.regenerate_reply(&compliance_result.redacted_text).await {
+                (Some(reply), attempts) => (GuardrailDecision::Regenerated, reply, attempts),
+                (None, attempts) => (GuardrailDecision::Redacted, compliance_result.redacted_text.clone(), attempts),
+            },
+        };
+        self.audit_decision(&message, &compliance_result, policy, decision, violations)?;
+        
+        Ok(GuardrailResult {
+            decision,
+            delivered_text,
+            compliance_result,
+            regenerate_attempts,
+        })
     }
     
-    fn redact_pii(&self, content: &str, detections: &[PiiDetection]) -> String {
-        let mut redacted = content.to_string();
-        let mut offset = 0;
+    fn audit_decision(
+        &self,
+        message: &ChatMessage,
+        compliance_result: &ComplianceResult,
+        policy: &CompliancePolicy,
+        decision: GuardrailDecision,
+        violations: Vec<ViolationType>,
+    ) -> Result<()> {
+        let Some(audit) = &self.audit else {
+            return Ok(());
+        };
+        let mut agent_ids = vec![self.agent_id.clone()];
+        agent_ids.extend(self.extractor.as_ref().map(|e| e.agent_id().to_string()));
         
-        let mut sorted_detections = detections.to_vec();
-        sorted_detections.sort_by_key(|d| d.start_pos);
+        audit.record(policy, agent_ids, Some(message), compliance_result, decision, violations)?;
+        Ok(())
+    }
+    
+    /// Asks the LLM to rewrite a redacted reply until a rewrite scans clean.
+    /// Only the redacted reply is sent. Returns the rewrite, if any, and the
+    /// number of attempts made.
+    async fn regenerate_reply(&self, redacted_reply: &str) -> (Option<String>, u32) {
+        let Some(backend) = &self.backend else {
+            return (None, 0);
+        };
+        let prompt = llm::render(&self.prompts.regeneration, &[("reply", redacted_reply.to_string())]);
         
-        for detection in sorted_detections {
-            let start = detection.start_pos + offset;
-            let end = detection.end_pos + offset;
-            
-            if start < redacted.len() && end <= redacted.len() {
-                let replacement = match detection.pii_type {
-                    crate::types::PiiType::Email => "[EMAIL]",
-                    crate::types::PiiType::PhoneNumber => "[PHONE]",
-                    crate::types::PiiType::SocialSecurityNumber => "[SSN]",
-                    crate::types::PiiType::CreditCardNumber => "[CC]",
-                    _ => "[REDACTED]",
-                };
-                
-                redacted.replace_range(start..end, replacement);
-                let new_offset = offset as i32 + replacement.len() as i32 - (end - start) as i32;
-                offset = new_offset.max(0) as usize;
+        let attempts = self.outbound_policy.guardrail.regenerate_attempts;
+        for attempt in 1..=attempts {
+            match backend.complete(&self.prompts.system, &prompt).await {
+                Ok(reply) => {
+                    let clean = self.detect_pii_in_message(&reply, &self.outbound_policy).await
+                        .is_ok_and(|detections| detections.is_empty());
+                    if clean {
+                        return (Some(reply), attempt);
+                    }
+                    tracing::warn!("Regenerated reply still discloses PII (attempt {} of {})", attempt, attempts);
+                }
+                Err(e) => {
+                    tracing::warn!("Reply regeneration failed, redacting instead: {:#}", e);
+                    return (None, attempt);
+                }
             }
         }
         
-        redacted
+        (None, attempts)
+    }
+    
+    fn build_compliance_result(&self, message: &ChatMessage, detections: Vec<PiiDetection>, policy: &CompliancePolicy) -> ComplianceResult {
+        let redaction = self.redact_pii(message, &detections, policy);
+        
+        ComplianceResult {
+            original_text: message.content.clone(),
+            redacted_text: redaction.text,
+            compliance_score: self.calculate_compliance_score(&detections),
+            recommendations: self.generate_recommendations(&detections, policy),
+            detected_pii: detections,
+            redaction_map: redaction.spans,
+            dismissed_pii: vec![],
+            cross_message_pii: vec![],
+        }
+    }
+    
+    async fn detect_pii_in_message(&self, content: &str, policy: &CompliancePolicy) -> Result<Vec<PiiDetection>> {
+        // Share the scanner's classifier so chat messages get the same
+        // validation and non-overlapping spans as the compliance pipeline
+        let mut detections = self.classifier.lock().await.detect_pii(content);
+        if let Some(extractor) = &self.extractor {
+            detections.extend(extractor.extract(content).await);
+            detections = resolve_spans(content, detections);
+        }
+        Ok(policy.filter(detections))
+    }
+    
+    fn redact_pii(&self, message: &ChatMessage, detections: &[PiiDetection], policy: &CompliancePolicy) -> RedactionOutput {
+        let scope = policy.pseudonymization.scope_key(&message.message_id, Some(&message.session_id));
+        let context = RedactionContext {
+            vault: self.vault.as_deref(),
+            pseudonymizer: self.pseudonymizer.as_deref(),
+            hasher: self.hasher.as_deref(),
+            synthesizer: self.synthesizer.as_deref(),
+            scope: &scope,
+        };
+        redaction::apply_policy(&message.content, detections, policy, &context)
     }
     
     fn calculate_compliance_score(&self, detections: &[PiiDetection]) -> f32 {
@@ -150,7 +546,7 @@ impl ChatbotComplianceAgent {
         (avg_confidence - pii_penalty).max(0.0)
     }
     
-    fn generate_recommendations(&self, detections: &[PiiDetection]) -> Vec<String> {
+    fn generate_recommendations(&self, detections: &[PiiDetection], policy: &CompliancePolicy) -> Vec<String> {
         let mut recommendations = vec![];
         
         if detections.is_empty() {
@@ -159,13 +555,13 @@ impl ChatbotComplianceAgent {
         }
         
         for detection in detections {
-            let severity = self.compliance_rules.get(&format!("{:?}", detection.pii_type))
-                .unwrap_or(&Severity::Medium);
+            let severity = policy.severity(&detection.pii_type);
             
             recommendations.push(format!(
-                "⚠️  {} detected (Severity: {:?}) - Consider redaction",
-                format!("{:?}", detection.pii_type),
-                severity
+                "⚠️  {:?} detected (Severity: {:?}) - Policy action: {:?}",
+                detection.pii_type,
+                severity,
+                policy.action(&detection.pii_type)
             ));
         }
         
@@ -175,43 +571,144 @@ impl ChatbotComplianceAgent {
         recommendations
     }
     
-    async fn update_session_compliance(&self, message: &ChatMessage, compliance_result: &ComplianceResult) -> Result<()> {
-        let mut sessions = self.active_sessions.lock().await;
+    async fn update_session_compliance(
+        &self,
+        message: &ChatMessage,
+        compliance_result: &ComplianceResult,
+        policy: &CompliancePolicy,
+        violation_type: ViolationType,
+        earlier_fragments: &[(String, PiiDetection)],
+    ) -> Result<Vec<ViolationType>> {
+        let _guard = self.session_lock.lock().await;
         
-        let session = sessions.entry(message.session_id.clone()).or_insert_with(|| ChatSession {
+        let mut session = self.sessions.load(&message.session_id)?.unwrap_or_else(|| ChatSession {
             session_id: message.session_id.clone(),
             user_id: message.user_id.clone(),
             messages: vec![],
             compliance_violations: vec![],
             risk_level: RiskLevel::Safe,
+            redacted_content: HashMap::new(),
+            purpose: None,
+            last_activity: chrono::Utc::now(),
         });
         
-        // Add message to session
+        let violations_before = session.compliance_violations.len();
+        
+        // Add message to session; the redacted text outlives the raw content
         session.messages.push(message.clone());
+        session.last_activity = chrono::Utc::now();
+        session.redacted_content.insert(message.message_id.clone(), compliance_result.redacted_text.clone());
         
-        // Check for compliance violations
-        if !compliance_result.detected_pii.is_empty() {
+        // Earlier parts of split PII are redacted in the messages they were sent in
+        let mut earlier_ids: Vec<&String> = vec![];
+        for (message_id, _) in earlier_fragments {
+            if !earlier_ids.contains(&message_id) {
+                earlier_ids.push(message_id);
+            }
+        }
+        for message_id in earlier_ids {
+            let Some(earlier) = session.messages.iter().find(|m| &m.message_id == message_id).cloned() else {
+                continue;
+            };
+            let mut detections = self.detect_pii_in_message(&earlier.content, policy).await?;
+            detections.extend(earlier_fragments.iter().filter(|(id, _)| id == message_id).map(|(_, f)| f.clone()));
+            let detections = resolve_spans(&earlier.content, detections);
+            let redacted = self.redact_pii(&earlier, &detections, policy).text;
+            
+            match session.redacted_content.get_mut(message_id) {
+                Some(stored) => *stored = redacted,
+                // The raw content was already purged, so the content is the redacted text
+                None => if let Some(stored) = session.messages.iter_mut().find(|m| &m.message_id == message_id) {
+                    stored.content = redacted;
+                },
+            }
+        }
+        
+        // Check for compliance violations; PII the policy allows is not one
+        let mut exposed: Vec<PiiDetection> = compliance_result.detected_pii.iter()
+            .filter(|d| policy.action(&d.pii_type) != PolicyAction::Allow)
+            .cloned()
+            .collect();
+        
+        // Split PII is attributed to every message it touches
+        let mut message_ids: Vec<String> = vec![];
+        let touched = compliance_result.cross_message_pii.iter()
+            .flat_map(|c| c.message_ids.iter())
+            .chain(std::iter::once(&message.message_id));
+        for message_id in touched {
+            if !message_ids.contains(message_id) {
+                message_ids.push(message_id.clone());
+            }
+        }
+        
+        let is_user_message = violation_type == ViolationType::PiiExposure;
+        let purpose = session.purpose.clone().unwrap_or_else(|| self.consent_purpose.clone());
+        
+        // Users may share what they consented to; anything else lacks consent
+        let mut violation_type = violation_type;
+        if let (true, Some(consent)) = (is_user_message, &self.consent) {
+            exposed.retain(|d| !consent.has_consent(&message.user_id, &d.pii_type, &purpose));
+            violation_type = ViolationType::ConsentMissing;
+        }
+        
+        if !exposed.is_empty() {
+            let description = match violation_type {
+                ViolationType::BotDisclosure => format!("PII disclosed by bot: {}", compliance_result.redacted_text),
+                ViolationType::ConsentMissing => format!(
+                    "PII shared without consent for purpose \"{}\": {}",
+                    purpose, compliance_result.redacted_text
+                ),
+                _ => format!("PII detected in message: {}", compliance_result.redacted_text),
+            };
+            
             let violation = ComplianceViolation {
-                violation_type: ViolationType::PiiExposure,
-                severity: self.determine_violation_severity(&compliance_result.detected_pii),
-                message: format!("PII detected in message: {}", message.content),
+                violation_type,
+                severity: self.determine_violation_severity(&exposed),
+                message: description,
                 timestamp: chrono::Utc::now(),
-                pii_detected: compliance_result.detected_pii.clone(),
+                pii_detected: scrub_values(exposed, policy),
+                message_ids: message_ids.clone(),
             };
             
             session.compliance_violations.push(violation);
-            session.risk_level = self.calculate_session_risk_level(session);
         }
         
-        Ok(())
+        // Collecting PII the declared purpose does not need is excessive,
+        // consented or not
+        let purpose_config = session.purpose.as_deref().and_then(|p| policy.purpose(p));
+        if let (true, Some(purpose_config)) = (is_user_message, purpose_config) {
+            let excessive: Vec<PiiDetection> = compliance_result.detected_pii.iter()
+                .filter(|d| !purpose_config.allowed.contains(&d.pii_type))
+                .cloned()
+                .collect();
+            
+            if !excessive.is_empty() {
+                let types: Vec<String> = excessive.iter().map(|d| format!("{:?}", d.pii_type)).collect();
+                session.compliance_violations.push(ComplianceViolation {
+                    violation_type: ViolationType::DataMinimization,
+                    severity: purpose_config.severity.clone(),
+                    message: format!("{} not needed for purpose \"{}\"", types.join(", "), purpose),
+                    timestamp: chrono::Utc::now(),
+                    pii_detected: scrub_values(excessive, policy),
+                    message_ids,
+                });
+            }
+        }
+        
+        let raised = session.compliance_violations[violations_before..].iter()
+            .map(|v| v.violation_type.clone())
+            .collect();
+        
+        session.risk_level = self.calculate_session_risk_level(&session);
+        self.sessions.save(&session)?;
+        Ok(raised)
     }
     
     fn determine_violation_severity(&self, detections: &[PiiDetection]) -> Severity {
         let mut max_severity = Severity::Low;
         
         for detection in detections {
-            let severity = self.compliance_rules.get(&format!("{:?}", detection.pii_type))
-                .unwrap_or(&Severity::Medium);
+            let severity = self.policy.severity(&detection.pii_type);
             
             max_severity = match (max_severity, severity) {
                 (Severity::Critical, _) | (_, Severity::Critical) => Severity::Critical,
@@ -242,6 +739,14 @@ impl ChatbotComplianceAgent {
     }
 }
 
+/// Violations outlive the raw content they were raised on, so they keep
+/// each detection's type and position but not its value
+fn scrub_values(detections: Vec<PiiDetection>, policy: &CompliancePolicy) -> Vec<PiiDetection> {
+    detections.into_iter()
+        .map(|d| PiiDetection { value: policy.replacement(&d.pii_type), ..d })
+        .collect()
+}
+
 #[async_trait]
 impl super::Agent for ChatbotComplianceAgent {
     async fn process(&self, message: AgentMessage, _context: &mut AgentContext) -> Result<AgentMessage> {
@@ -251,7 +756,13 @@ impl super::Agent for ChatbotComplianceAgent {
                     message.payload["chat_message"].clone()
                 ).unwrap_or_default();
                 
-                let compliance_result = self.process_chat_message(chat_message.clone()).await?;
+                // Bot replies go through the outbound guardrail
+                let (compliance_result, guardrail) = if chat_message.is_user_message {
+                    (self.process_chat_message(chat_message.clone()).await?, None)
+                } else {
+                    let guardrail = self.process_bot_message(chat_message.clone()).await?;
+                    (guardrail.compliance_result.clone(), Some(guardrail))
+                };
                 
                 let result = AgentMessage {
                     agent_id: self.agent_id.clone(),
@@ -259,9 +770,8 @@ impl super::Agent for ChatbotComplianceAgent {
                     payload: json!({
                         "chat_message": chat_message,
                         "compliance_result": compliance_result,
-                        "session_risk_level": self.active_sessions.lock().await
-                            .get(&chat_message.session_id)
-                            .map(|s| s.risk_level.clone()),
+                        "guardrail": guardrail,
+                        "session_risk_level": self.sessions.risk_level(&chat_message.session_id)?,
                         "timestamp": chrono::Utc::now(),
                     }),
                     timestamp: chrono::Utc::now(),
@@ -284,4 +794,232 @@ impl Default for ChatbotComplianceAgent {
     fn default() -> Self {
         Self::new()
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::PiiType;
+
+    struct Canned(&'static str);
+
+    #[async_trait]
+    impl LlmBackend for Canned {
+        async fn complete(&self, _system: &str, _user: &str) -> Result<String> {
+            Ok(self.0.to_string())
+        }
+
+        fn model(&self) -> &str {
+            "canned"
+        }
+    }
+
+    /// Agent whose bot replies go through `action`, regenerated by `reply`
+    fn guarded_agent(action: GuardrailAction, reply: Option<&'static str>) -> ChatbotComplianceAgent {
+        let mut policy = CompliancePolicy::default();
+        policy.guardrail.action = action;
+        let backend = reply.map(|reply| Arc::new(Canned(reply)) as Arc<dyn LlmBackend>);
+
+        ChatbotComplianceAgent::new()
+            .with_policy(Arc::new(policy))
+            .with_backend(backend, PromptTemplates::default())
+    }
+
+    fn bot_message(content: &str) -> ChatMessage {
+        ChatMessage {
+            is_user_message: false,
+            ..user_message("b1", content)
+        }
+    }
+
+    const LEAKY_REPLY: &str = "Sure, her email is jane.doe@corp.com";
+
+    fn user_message(message_id: &str, content: &str) -> ChatMessage {
+        ChatMessage {
+            user_id: "u1".to_string(),
+            session_id: "s1".to_string(),
+            message_id: message_id.to_string(),
+            timestamp: chrono::Utc::now(),
+            content: content.to_string(),
+            is_user_message: true,
+        }
+    }
+
+    /// Sends `parts` as consecutive user messages m1, m2, ... and returns the
+    /// result for the last one
+    async fn send_split(agent: &ChatbotComplianceAgent, parts: &[&str]) -> ComplianceResult {
+        let mut result = None;
+        for (i, part) in parts.iter().enumerate() {
+            result = Some(agent.process_chat_message(user_message(&format!("m{}", i + 1), part)).await.unwrap());
+        }
+        result.unwrap()
+    }
+
+    fn assert_split_pii_redacted(session: &ChatSession, pii_type: PiiType, message_ids: &[&str], digits: &[&str]) {
+        let violation = session.compliance_violations.iter()
+            .find(|v| v.pii_detected.iter().any(|d| d.pii_type == pii_type))
+            .expect("split PII raises a violation");
+        assert_eq!(violation.message_ids, message_ids);
+
+        for message_id in message_ids {
+            let redacted = &session.redacted_content[*message_id];
+            assert!(!digits.iter().any(|d| redacted.contains(d)), "{}: {}", message_id, redacted);
+        }
+    }
+
+    #[tokio::test]
+    async fn ssn_split_over_two_messages_is_redacted_in_both() {
+        let sessions = Arc::new(InMemorySessionStore::default());
+        let agent = ChatbotComplianceAgent::new().with_session_store(sessions.clone());
+
+        let result = send_split(&agent, &["my SSN is 123", "45-6789"]).await;
+
+        assert_eq!(result.cross_message_pii.len(), 1);
+        let split = &result.cross_message_pii[0];
+        assert_eq!(split.detection.pii_type, PiiType::SocialSecurityNumber);
+        assert_eq!(split.detection.value, "123 45-6789");
+        assert_eq!(split.message_ids, ["m1", "m2"]);
+        assert!(!result.redacted_text.contains("6789"), "{}", result.redacted_text);
+
+        let session = sessions.load("s1").unwrap().unwrap();
+        assert_split_pii_redacted(&session, PiiType::SocialSecurityNumber, &["m1", "m2"], &["123", "45-6789"]);
+        assert!(session.redacted_content["m1"].starts_with("my SSN is "));
+    }
+
+    #[tokio::test]
+    async fn card_split_over_three_messages_is_redacted_in_each() {
+        let sessions = Arc::new(InMemorySessionStore::default());
+        let agent = ChatbotComplianceAgent::new().with_session_store(sessions.clone());
+
+        let result = send_split(&agent, &["my card is 4111", "1111 1111", "1111"]).await;
+
+        assert_eq!(result.cross_message_pii.len(), 1);
+        let split = &result.cross_message_pii[0];
+        assert_eq!(split.detection.pii_type, PiiType::CreditCardNumber);
+        assert_eq!(split.detection.value, "4111 1111 1111 1111");
+        assert_eq!(split.message_ids, ["m1", "m2", "m3"]);
+
+        let session = sessions.load("s1").unwrap().unwrap();
+        assert_split_pii_redacted(&session, PiiType::CreditCardNumber, &["m1", "m2", "m3"], &["4111", "1111"]);
+    }
+
+    #[tokio::test]
+    async fn sweep_leaves_no_raw_value_in_the_stored_session() {
+        let mut policy = CompliancePolicy::default();
+        policy.retention.raw_content_hours = 0;
+        let sessions = Arc::new(InMemorySessionStore::default());
+        let agent = ChatbotComplianceAgent::new()
+            .with_policy(Arc::new(policy.clone()))
+            .with_session_store(sessions.clone());
+
+        let ssn = "123-45-6789";
+        let email = "jane.doe@corp.com";
+        agent.process_chat_message(ChatMessage {
+            user_id: "u1".to_string(),
+            session_id: "s1".to_string(),
+            message_id: "m1".to_string(),
+            timestamp: chrono::Utc::now() - chrono::Duration::minutes(1),
+            content: format!("My SSN is {}", ssn),
+            is_user_message: true,
+        }).await.unwrap();
+
+        // A violation stored with its raw value, as before values were scrubbed on write
+        let mut session = sessions.load("s1").unwrap().unwrap();
+        assert!(!session.compliance_violations.is_empty());
+        session.compliance_violations.push(ComplianceViolation {
+            violation_type: ViolationType::PiiExposure,
+            severity: Severity::Medium,
+            message: "PII detected in message: [EMAIL]".to_string(),
+            timestamp: chrono::Utc::now(),
+            pii_detected: vec![PiiDetection {
+                pii_type: PiiType::Email,
+                value: email.to_string(),
+                ..Default::default()
+            }],
+            message_ids: vec!["m0".to_string()],
+        });
+        sessions.save(&session).unwrap();
+
+        let report = agent.sweep_retention().await.unwrap();
+        assert_eq!(report.raw_purged, 1);
+        assert_eq!(report.values_scrubbed, 1);
+
+        let stored = serde_json::to_string(&sessions.load("s1").unwrap().unwrap()).unwrap();
+        assert!(!stored.contains(ssn) && !stored.contains("6789"), "{}", stored);
+        assert!(!stored.contains(email), "{}", stored);
+    }
+
+    #[tokio::test]
+    async fn pii_outside_the_session_purpose_raises_data_minimization() {
+        let agent = ChatbotComplianceAgent::new();
+        agent.set_session_purpose("s1", "u1", "support").await.unwrap();
+        let minimization = |session: &ChatSession| -> Vec<ComplianceViolation> {
+            session.compliance_violations.iter()
+                .filter(|v| v.violation_type == ViolationType::DataMinimization)
+                .cloned()
+                .collect()
+        };
+
+        // Support may collect emails
+        agent.process_chat_message(user_message("m1", "Reach me at jane.doe@corp.com")).await.unwrap();
+        assert!(minimization(&agent.session("s1").unwrap().unwrap()).is_empty());
+
+        agent.process_chat_message(user_message("m2", "My SSN is 123-45-6789")).await.unwrap();
+        let raised = minimization(&agent.session("s1").unwrap().unwrap());
+        assert_eq!(raised.len(), 1);
+        assert_eq!(raised[0].severity, Severity::Medium);
+        assert_eq!(raised[0].message_ids, ["m2"]);
+        assert_eq!(raised[0].pii_detected[0].pii_type, PiiType::SocialSecurityNumber);
+    }
+
+    #[tokio::test]
+    async fn clean_bot_reply_passes_unchanged() {
+        let agent = guarded_agent(GuardrailAction::Block, None);
+        let result = agent.process_bot_message(bot_message("Your order has shipped.")).await.unwrap();
+
+        assert_eq!(result.decision, GuardrailDecision::Passed);
+        assert_eq!(result.delivered_text, "Your order has shipped.");
+    }
+
+    #[tokio::test]
+    async fn guardrail_blocks_a_disclosing_reply() {
+        let agent = guarded_agent(GuardrailAction::Block, None);
+        let result = agent.process_bot_message(bot_message(LEAKY_REPLY)).await.unwrap();
+
+        assert_eq!(result.decision, GuardrailDecision::Blocked);
+        assert_eq!(result.delivered_text, CompliancePolicy::default().guardrail.block_message);
+
+        let session = agent.session("s1").unwrap().unwrap();
+        assert!(session.compliance_violations.iter().any(|v| v.violation_type == ViolationType::BotDisclosure));
+    }
+
+    #[tokio::test]
+    async fn guardrail_redacts_a_disclosing_reply() {
+        let agent = guarded_agent(GuardrailAction::Redact, None);
+        let result = agent.process_bot_message(bot_message(LEAKY_REPLY)).await.unwrap();
+
+        assert_eq!(result.decision, GuardrailDecision::Redacted);
+        assert!(result.delivered_text.starts_with("Sure, her email is "));
+        assert!(!result.delivered_text.contains("jane.doe@corp.com"), "{}", result.delivered_text);
+    }
+
+    #[tokio::test]
+    async fn guardrail_delivers_a_clean_regenerated_reply() {
+        let agent = guarded_agent(GuardrailAction::Regenerate, Some("You can reach her through the team inbox."));
+        let result = agent.process_bot_message(bot_message(LEAKY_REPLY)).await.unwrap();
+
+        assert_eq!(result.decision, GuardrailDecision::Regenerated);
+        assert_eq!(result.delivered_text, "You can reach her through the team inbox.");
+        assert_eq!(result.regenerate_attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn guardrail_redacts_when_every_regeneration_still_discloses() {
+        let agent = guarded_agent(GuardrailAction::Regenerate, Some("Try john.roe@corp.com instead"));
+        let result = agent.process_bot_message(bot_message(LEAKY_REPLY)).await.unwrap();
+
+        assert_eq!(result.decision, GuardrailDecision::Redacted);
+        assert_eq!(result.regenerate_attempts, CompliancePolicy::default().guardrail.regenerate_attempts);
+        assert!(!result.delivered_text.contains("@corp.com"), "{}", result.delivered_text);
+    }
+}
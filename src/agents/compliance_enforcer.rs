@@ -1,71 +1,82 @@
+use crate::models::resolve_spans;
+use crate::policy::CompliancePolicy;
+use crate::redaction::{self, RedactionContext, RedactionOutput};
 use crate::types::{AgentContext, AgentMessage, MessageType, PiiDetection, PiiType};
+use crate::hashing::KeyedHasher;
+use crate::pseudonym::Pseudonymizer;
+use crate::synthetic::Synthesizer;
+use crate::vault::TokenVault;
 use anyhow::Result;
 use async_trait::async_trait;
 use serde_json::json;
 use std::collections::HashMap;
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct ComplianceEnforcerAgent {
     agent_id: String,
-    redaction_rules: HashMap<PiiType, String>,
-    masking_patterns: HashMap<PiiType, String>,
+    policy: Arc<CompliancePolicy>,
+    vault: Option<Arc<TokenVault>>,
+    pseudonymizer: Option<Arc<Pseudonymizer>>,
+    hasher: Option<Arc<KeyedHasher>>,
+    synthesizer: Option<Arc<Synthesizer>>,
 }
 
 impl ComplianceEnforcerAgent {
     pub fn new() -> Self {
-        let mut redaction_rules = HashMap::new();
-        redaction_rules.insert(PiiType::Email, "[EMAIL]".to_string());
-        redaction_rules.insert(PiiType::PhoneNumber, "[PHONE]".to_string());
-        redaction_rules.insert(PiiType::SocialSecurityNumber, "[SSN]".to_string());
-        redaction_rules.insert(PiiType::CreditCardNumber, "[CC]".to_string());
-        redaction_rules.insert(PiiType::IpAddress, "[IP]".to_string());
-        redaction_rules.insert(PiiType::DateOfBirth, "[DOB]".to_string());
-        redaction_rules.insert(PiiType::Address, "[ADDRESS]".to_string());
-        redaction_rules.insert(PiiType::Name, "[NAME]".to_string());
-        redaction_rules.insert(PiiType::Unknown, "[PII]".to_string());
-        
-        let mut masking_patterns = HashMap::new();
-        masking_patterns.insert(PiiType::Email, "***@***.***".to_string());
-        masking_patterns.insert(PiiType::PhoneNumber, "***-***-****".to_string());
-        masking_patterns.insert(PiiType::SocialSecurityNumber, "***-**-****".to_string());
-        masking_patterns.insert(PiiType::CreditCardNumber, "****-****-****-****".to_string());
-        masking_patterns.insert(PiiType::IpAddress, "***.***.***.***".to_string());
-        masking_patterns.insert(PiiType::DateOfBirth, "**/**/****".to_string());
-        masking_patterns.insert(PiiType::Address, "[ADDRESS REDACTED]".to_string());
-        masking_patterns.insert(PiiType::Name, "*** ***".to_string());
-        masking_patterns.insert(PiiType::Unknown, "[REDACTED]".to_string());
-        
         Self {
             agent_id: "compliance-enforcer-001".to_string(),
-            redaction_rules,
-            masking_patterns,
+            policy: Arc::new(CompliancePolicy::default()),
+            vault: None,
+            pseudonymizer: None,
+            hasher: None,
+            synthesizer: None,
         }
     }
     
-    fn apply_redaction(&self, text: &str, detections: &[PiiDetection]) -> String {
-        let mut redacted_text = text.to_string();
-        let mut offset = 0;
-        
-        // Sort detections by start position to handle overlapping
-        let mut sorted_detections = detections.to_vec();
-        sorted_detections.sort_by_key(|d| d.start_pos);
-        
-        for detection in sorted_detections {
-            let start = detection.start_pos + offset;
-            let end = detection.end_pos + offset;
-            
-            if start < redacted_text.len() && end <= redacted_text.len() {
-                let replacement = self.redaction_rules.get(&detection.pii_type)
-                    .unwrap_or(&"[REDACTED]".to_string())
-                    .clone();
-                
-                redacted_text.replace_range(start..end, &replacement);
-                let new_offset = offset as i32 + replacement.len() as i32 - (end - start) as i32;
-                offset = new_offset.max(0) as usize;
-            }
-        }
-        
-        redacted_text
+    pub fn with_policy(mut self, policy: Arc<CompliancePolicy>) -> Self {
+        self.policy = policy;
+        self
+    }
+    
+    pub fn policy(&self) -> &CompliancePolicy {
+        &self.policy
+    }
+    
+    /// Vault for the `tokenize` action; without one tokenized values are redacted
+    pub fn with_vault(mut self, vault: Option<Arc<TokenVault>>) -> Self {
+        self.vault = vault;
+        self
+    }
+    
+    /// Keys the `pseudonymize` action; without one pseudonymized values are redacted
+    pub fn with_pseudonymizer(mut self, pseudonymizer: Option<Arc<Pseudonymizer>>) -> Self {
+        self.pseudonymizer = pseudonymizer;
+        self
+    }
+    
+    /// Keys the `hash` action; without one hashed values are redacted
+    pub fn with_hasher(mut self, hasher: Option<Arc<KeyedHasher>>) -> Self {
+        self.hasher = hasher;
+        self
+    }
+    
+    /// Keys the `synthesize` action; without one synthesized values are redacted
+    pub fn with_synthesizer(mut self, synthesizer: Option<Arc<Synthesizer>>) -> Self {
+        self.synthesizer = synthesizer;
+        self
+    }
+    
+    fn apply_redaction(&self, text: &str, detections: &[PiiDetection], session_id: Option<&str>) -> RedactionOutput {
+        let scope = self.policy.pseudonymization.scope_key(&uuid::Uuid::new_v4().to_string(), session_id);
+        let context = RedactionContext {
+            vault: self.vault.as_deref(),
+            pseudonymizer: self.pseudonymizer.as_deref(),
+            hasher: self.hasher.as_deref(),
+            synthesizer: self.synthesizer.as_deref(),
+            scope: &scope,
+        };
+        redaction::apply_policy(text, detections, &self.policy, &context)
     }
     
     fn calculate_compliance_score(&self, detections: &[PiiDetection]) -> f32 {
@@ -122,7 +133,11 @@ impl crate::agents::Agent for ComplianceEnforcerAgent {
                     message.payload["detections"].clone()
                 ).unwrap_or_default();
                 
-                let redacted_text = self.apply_redaction(text, &detections);
+                // Detections may come from any producer, so never splice overlapping spans
+                let detections = self.policy.filter(resolve_spans(text, detections));
+                
+                let session_id = message.payload["session_id"].as_str();
+                let redaction = self.apply_redaction(text, &detections, session_id);
                 let compliance_score = self.calculate_compliance_score(&detections);
                 let recommendations = self.generate_recommendations(&detections);
                 
@@ -131,10 +146,12 @@ impl crate::agents::Agent for ComplianceEnforcerAgent {
                     message_type: MessageType::ComplianceEnforcementResult,
                     payload: json!({
                         "original_text": text,
-                        "redacted_text": redacted_text,
+                        "redacted_text": redaction.text,
+                        "redaction_map": redaction.spans,
                         "detected_pii": detections,
                         "compliance_score": compliance_score,
                         "recommendations": recommendations,
+                        "policy_version": self.policy.version,
                         "enforcement_timestamp": chrono::Utc::now(),
                     }),
                     timestamp: chrono::Utc::now(),
@@ -0,0 +1,210 @@
+use crate::llm::{self, ExtractionConfig, LlmBackend, LlmSetup, PromptTemplates};
+use crate::types::{AgentContext, AgentMessage, MessageType, PiiDetection, PiiType};
+use crate::utils::corpus;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+
+/// Finds names, addresses, dates of birth and other free-form PII the
+/// pattern scanner has no rule for. The LLM returns spans as JSON; each span
+/// is checked against the source text before it becomes a detection.
+#[derive(Clone)]
+pub struct LlmExtractorAgent {
+    agent_id: String,
+    backend: Arc<dyn LlmBackend>,
+    prompts: PromptTemplates,
+    config: ExtractionConfig,
+}
+
+#[derive(Deserialize)]
+struct ExtractionReply {
+    #[serde(default)]
+    entities: Vec<ExtractedEntity>,
+}
+
+#[derive(Deserialize)]
+struct ExtractedEntity {
+    #[serde(rename = "type")]
+    label: String,
+    text: String,
+}
+
+impl LlmExtractorAgent {
+    pub fn new(backend: Arc<dyn LlmBackend>, prompts: PromptTemplates, config: ExtractionConfig) -> Self {
+        Self {
+            agent_id: "llm-extractor-001".to_string(),
+            backend,
+            prompts,
+            config,
+        }
+    }
+
+    /// An extractor when the LLM config has a backend and enables extraction
+    pub fn from_setup(setup: &LlmSetup) -> Option<Self> {
+        let backend = setup.backend.clone().filter(|_| setup.extraction.enabled)?;
+        Some(Self::new(backend, setup.prompts.clone(), setup.extraction.clone()))
+    }
+
+    /// Detections for the spans the LLM found. Spans whose text does not occur
+    /// in `text`, or whose type was not asked for, are discarded. Errors are
+    /// logged and yield no detections, leaving the pattern scanner's result.
+    pub async fn extract(&self, text: &str) -> Vec<PiiDetection> {
+        if text.trim().is_empty() || self.config.types.is_empty() {
+            return vec![];
+        }
+
+        let types = self.config.types.iter().map(label).collect::<Vec<_>>().join(", ");
+        let prompt = llm::render(&self.prompts.extraction, &[
+            ("types", types),
+            ("text", text.to_string()),
+        ]);
+
+        let reply = self.backend.complete(&self.prompts.system, &prompt).await
+            .and_then(|reply| llm::parse_json_reply::<ExtractionReply>(&reply));
+
+        match reply {
+            Ok(reply) => reply.entities.into_iter()
+                .flat_map(|entity| self.locate(text, entity))
+                .collect(),
+            Err(e) => {
+                tracing::warn!("LLM extraction failed, using pattern detections only: {:#}", e);
+                vec![]
+            }
+        }
+    }
+
+    /// Anchors an entity to the source text. A value the text repeats is
+    /// PII wherever it appears, so every whole-word occurrence is marked.
+    fn locate(&self, text: &str, entity: ExtractedEntity) -> Vec<PiiDetection> {
+        let Some(pii_type) = corpus::parse_label(&entity.label).filter(|t| self.config.types.contains(t)) else {
+            tracing::debug!("LLM extraction returned unrequested type {:?}", entity.label);
+            return vec![];
+        };
+        let value = entity.text.trim();
+        if value.is_empty() {
+            return vec![];
+        }
+
+        let detections: Vec<PiiDetection> = text.match_indices(value)
+            .map(|(start_pos, _)| (start_pos, start_pos + value.len()))
+            .filter(|&(start_pos, end_pos)| on_word_boundaries(text, start_pos, end_pos))
+            .map(|(start_pos, end_pos)| PiiDetection {
+                pii_type: pii_type.clone(),
+                confidence: self.config.confidence,
+                start_pos,
+                end_pos,
+                value: value.to_string(),
+                card_network: None,
+                verification: None,
+            })
+            .collect();
+        if detections.is_empty() {
+            tracing::debug!("LLM extraction returned a {:?} span not found in the text", pii_type);
+        }
+        detections
+    }
+}
+
+/// False when the span starts or ends inside a word, e.g. "Ann" in "Annual"
+fn on_word_boundaries(text: &str, start: usize, end: usize) -> bool {
+    let joins = |outside: Option<char>, inside: Option<char>| {
+        outside.zip(inside).is_some_and(|(a, b)| a.is_alphanumeric() && b.is_alphanumeric())
+    };
+    let span = &text[start..end];
+    !joins(text[..start].chars().next_back(), span.chars().next())
+        && !joins(text[end..].chars().next(), span.chars().next_back())
+}
+
+/// Label used in the prompt; each one maps back through `corpus::parse_label`
+fn label(pii_type: &PiiType) -> &'static str {
+    match pii_type {
+        PiiType::Email => "email",
+        PiiType::PhoneNumber => "phone_number",
+        PiiType::SocialSecurityNumber => "ssn",
+        PiiType::IndividualTaxpayerNumber => "itin",
+        PiiType::EmployerIdentificationNumber => "ein",
+        PiiType::CreditCardNumber => "credit_card",
+        PiiType::IpAddress => "ip_address",
+        PiiType::DateOfBirth => "date_of_birth",
+        PiiType::Address => "address",
+        PiiType::Name => "name",
+        PiiType::Unknown => "pii",
+    }
+}
+
+#[async_trait]
+impl super::Agent for LlmExtractorAgent {
+    async fn process(&self, message: AgentMessage, _context: &mut AgentContext) -> Result<AgentMessage> {
+        match message.message_type {
+            MessageType::PiiExtractionRequest => {
+                let text = message.payload["text"].as_str().unwrap_or("");
+                let detections = self.extract(text).await;
+
+                Ok(AgentMessage {
+                    agent_id: self.agent_id.clone(),
+                    message_type: MessageType::PiiExtractionResult,
+                    payload: json!({
+                        "detections": detections,
+                        "text": text,
+                        "model": self.backend.model(),
+                        "extraction_timestamp": chrono::Utc::now(),
+                    }),
+                    timestamp: chrono::Utc::now(),
+                })
+            }
+            _ => {
+                Err(anyhow::anyhow!("Unsupported message type for LLM extractor"))
+            }
+        }
+    }
+
+    fn agent_id(&self) -> &str {
+        &self.agent_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Canned(&'static str);
+
+    #[async_trait]
+    impl LlmBackend for Canned {
+        async fn complete(&self, _system: &str, _user: &str) -> Result<String> {
+            Ok(self.0.to_string())
+        }
+
+        fn model(&self) -> &str {
+            "canned"
+        }
+    }
+
+    fn extractor(reply: &'static str) -> LlmExtractorAgent {
+        LlmExtractorAgent::new(Arc::new(Canned(reply)), PromptTemplates::default(), ExtractionConfig::default())
+    }
+
+    #[tokio::test]
+    async fn extract_marks_every_whole_word_occurrence() {
+        let text = "Ann called. Annual review: Ann's manager met ann.";
+        let reply = "Sure!\n```json\n{\"entities\": [{\"type\": \"name\", \"text\": \"Ann\"}]}\n```";
+        let detections = extractor(reply).extract(text).await;
+
+        let starts: Vec<usize> = detections.iter().map(|d| d.start_pos).collect();
+        assert_eq!(starts, vec![0, 27]);
+        assert!(detections.iter().all(|d| &text[d.start_pos..d.end_pos] == "Ann" && d.pii_type == PiiType::Name));
+    }
+
+    #[tokio::test]
+    async fn extract_drops_unrequested_types_and_invented_spans() {
+        let reply = r#"{"entities": [{"type": "ssn", "text": "123-45-6789"}, {"type": "name", "text": "Bob"}]}"#;
+        assert!(extractor(reply).extract("SSN 123-45-6789 on file").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn extract_yields_nothing_for_an_unreadable_reply() {
+        assert!(extractor("I cannot help with that.").extract("Ann called").await.is_empty());
+    }
+}
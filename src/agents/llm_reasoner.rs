@@ -1,17 +1,63 @@
+use crate::llm::{self, LlmBackend, PromptTemplates};
 use crate::types::{AgentContext, AgentMessage, ComplianceResult, MessageType};
 use anyhow::Result;
 use async_trait::async_trait;
 use serde_json::json;
+use std::collections::BTreeSet;
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct LlmReasonerAgent {
     agent_id: String,
+    backend: Option<Arc<dyn LlmBackend>>,
+    prompts: PromptTemplates,
 }
 
 impl LlmReasonerAgent {
     pub fn new() -> Self {
         Self {
             agent_id: "llm-reasoner-001".to_string(),
+            backend: None,
+            prompts: PromptTemplates::default(),
+        }
+    }
+    
+    /// Explains results with `backend`; without one, `generate_explanation` is used
+    pub fn with_backend(mut self, backend: Option<Arc<dyn LlmBackend>>, prompts: PromptTemplates) -> Self {
+        self.backend = backend;
+        self.prompts = prompts;
+        self
+    }
+    
+    /// Asks the backend for an explanation, falling back to the template text
+    /// when there is no backend or it fails. Returns the text and its source.
+    pub async fn explain(&self, compliance_result: &ComplianceResult) -> (String, &'static str) {
+        let Some(backend) = &self.backend else {
+            return (self.generate_explanation(compliance_result), "template");
+        };
+        
+        // Only the redacted text leaves the process
+        let pii_types: BTreeSet<String> = compliance_result.detected_pii.iter()
+            .map(|d| format!("{:?}", d.pii_type))
+            .collect();
+        let pii_types = if pii_types.is_empty() {
+            "none".to_string()
+        } else {
+            pii_types.into_iter().collect::<Vec<_>>().join(", ")
+        };
+        let prompt = llm::render(&self.prompts.explanation, &[
+            ("pii_count", compliance_result.detected_pii.len().to_string()),
+            ("pii_types", pii_types),
+            ("compliance_score", format!("{:.1}", compliance_result.compliance_score * 100.0)),
+            ("redacted_text", compliance_result.redacted_text.clone()),
+        ]);
+        
+        match backend.complete(&self.prompts.system, &prompt).await {
+            Ok(explanation) => (explanation, "llm"),
+            Err(e) => {
+                tracing::warn!("LLM explanation failed, using template: {:#}", e);
+                (self.generate_explanation(compliance_result), "template")
+            }
         }
     }
     
@@ -42,7 +88,7 @@ impl super::Agent for LlmReasonerAgent {
                 ).unwrap_or_default();
                 
                 // Generate explanation
-                let explanation = self.generate_explanation(&compliance_result);
+                let (explanation, source) = self.explain(&compliance_result).await;
                 
                 let result = AgentMessage {
                     agent_id: self.agent_id.clone(),
@@ -50,6 +96,8 @@ impl super::Agent for LlmReasonerAgent {
                     payload: json!({
                         "compliance_result": compliance_result,
                         "llm_explanation": explanation,
+                        "explanation_source": source,
+                        "llm_model": self.backend.as_ref().map(|b| b.model().to_string()),
                         "reasoning_timestamp": chrono::Utc::now(),
                     }),
                     timestamp: chrono::Utc::now(),
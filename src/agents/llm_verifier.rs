@@ -0,0 +1,230 @@
+use crate::llm::{self, LlmBackend, LlmSetup, PromptTemplates, VerificationConfig};
+use crate::types::{AgentContext, AgentMessage, MessageType, PiiDetection, Verdict, Verification};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+
+/// Second-pass check of ambiguous detections: each one is sent to the LLM
+/// with its surrounding text, and the LLM decides whether to keep or drop it.
+/// Every reviewed detection carries the verdict and rationale.
+#[derive(Clone)]
+pub struct LlmVerifierAgent {
+    agent_id: String,
+    backend: Arc<dyn LlmBackend>,
+    prompts: PromptTemplates,
+    config: VerificationConfig,
+}
+
+#[derive(Deserialize)]
+struct VerdictReply {
+    verdict: Verdict,
+    #[serde(default)]
+    rationale: String,
+}
+
+impl LlmVerifierAgent {
+    pub fn new(backend: Arc<dyn LlmBackend>, prompts: PromptTemplates, config: VerificationConfig) -> Self {
+        Self {
+            agent_id: "llm-verifier-001".to_string(),
+            backend,
+            prompts,
+            config,
+        }
+    }
+
+    /// A verifier when the LLM config has a backend and enables verification
+    pub fn from_setup(setup: &LlmSetup) -> Option<Self> {
+        let backend = setup.backend.clone().filter(|_| setup.verification.enabled)?;
+        Some(Self::new(backend, setup.prompts.clone(), setup.verification.clone()))
+    }
+
+    fn is_ambiguous(&self, detection: &PiiDetection) -> bool {
+        detection.confidence < self.config.below_confidence
+    }
+
+    /// Splits detections into kept and dismissed. Detections that are not
+    /// ambiguous pass through untouched; if the LLM fails or answers
+    /// off-format, the detection is kept, since dropping PII on an error
+    /// would leak it.
+    pub async fn verify(&self, text: &str, detections: Vec<PiiDetection>) -> (Vec<PiiDetection>, Vec<PiiDetection>) {
+        let mut kept = vec![];
+        let mut dismissed = vec![];
+
+        for mut detection in detections {
+            if !self.is_ambiguous(&detection) {
+                kept.push(detection);
+                continue;
+            }
+
+            let verification = self.ask(text, &detection).await;
+            let verdict = verification.verdict;
+            detection.verification = Some(verification);
+            match verdict {
+                Verdict::Keep => kept.push(detection),
+                Verdict::Drop => dismissed.push(detection),
+            }
+        }
+
+        (kept, dismissed)
+    }
+
+    async fn ask(&self, text: &str, detection: &PiiDetection) -> Verification {
+        let prompt = llm::render(&self.prompts.verification, &[
+            ("pii_type", format!("{:?}", detection.pii_type)),
+            ("value", detection.value.clone()),
+            ("context", context_window(text, detection.start_pos, detection.end_pos, self.config.context_chars)),
+        ]);
+
+        let reply = self.backend.complete(&self.prompts.system, &prompt).await
+            .and_then(|reply| llm::parse_json_reply::<VerdictReply>(&reply));
+
+        match reply {
+            Ok(reply) => Verification {
+                verdict: reply.verdict,
+                rationale: reply.rationale,
+                model: self.backend.model().to_string(),
+            },
+            Err(e) => {
+                tracing::warn!("LLM verification failed, keeping detection: {:#}", e);
+                Verification {
+                    verdict: Verdict::Keep,
+                    rationale: format!("kept without review: {}", e),
+                    model: self.backend.model().to_string(),
+                }
+            }
+        }
+    }
+}
+
+/// Up to `chars` characters either side of the span, snapped to char boundaries
+fn context_window(text: &str, start: usize, end: usize, chars: usize) -> String {
+    let before = text[..start.min(text.len())]
+        .char_indices()
+        .rev()
+        .nth(chars.saturating_sub(1))
+        .map_or(0, |(i, _)| i);
+    let after = text[end.min(text.len())..]
+        .char_indices()
+        .nth(chars)
+        .map_or(text.len(), |(i, _)| end + i);
+    text[before..after].to_string()
+}
+
+#[async_trait]
+impl super::Agent for LlmVerifierAgent {
+    async fn process(&self, message: AgentMessage, _context: &mut AgentContext) -> Result<AgentMessage> {
+        match message.message_type {
+            MessageType::PiiVerificationRequest => {
+                let text = message.payload["text"].as_str().unwrap_or("");
+                let detections: Vec<PiiDetection> = serde_json::from_value(
+                    message.payload["detections"].clone()
+                ).unwrap_or_default();
+
+                let (kept, dismissed) = self.verify(text, detections).await;
+
+                Ok(AgentMessage {
+                    agent_id: self.agent_id.clone(),
+                    message_type: MessageType::PiiVerificationResult,
+                    payload: json!({
+                        "detections": kept,
+                        "dismissed": dismissed,
+                        "model": self.backend.model(),
+                        "verification_timestamp": chrono::Utc::now(),
+                    }),
+                    timestamp: chrono::Utc::now(),
+                })
+            }
+            _ => {
+                Err(anyhow::anyhow!("Unsupported message type for LLM verifier"))
+            }
+        }
+    }
+
+    fn agent_id(&self) -> &str {
+        &self.agent_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::PiiType;
+
+    struct Canned(&'static str);
+
+    #[async_trait]
+    impl LlmBackend for Canned {
+        async fn complete(&self, _system: &str, _user: &str) -> Result<String> {
+            Ok(self.0.to_string())
+        }
+
+        fn model(&self) -> &str {
+            "canned"
+        }
+    }
+
+    struct Unreachable;
+
+    #[async_trait]
+    impl LlmBackend for Unreachable {
+        async fn complete(&self, _system: &str, _user: &str) -> Result<String> {
+            anyhow::bail!("connection refused")
+        }
+
+        fn model(&self) -> &str {
+            "unreachable"
+        }
+    }
+
+    const TEXT: &str = "Upgrade to build 555-123-4567 tonight";
+
+    fn ambiguous_phone() -> PiiDetection {
+        PiiDetection {
+            pii_type: PiiType::PhoneNumber,
+            confidence: 0.6,
+            start_pos: 17,
+            end_pos: 29,
+            value: "555-123-4567".to_string(),
+            ..Default::default()
+        }
+    }
+
+    async fn verify_with(backend: impl LlmBackend + 'static) -> (Vec<PiiDetection>, Vec<PiiDetection>) {
+        LlmVerifierAgent::new(Arc::new(backend), PromptTemplates::default(), VerificationConfig::default())
+            .verify(TEXT, vec![ambiguous_phone()])
+            .await
+    }
+
+    #[tokio::test]
+    async fn keep_verdict_keeps_the_detection() {
+        let (kept, dismissed) = verify_with(Canned(r#"{"verdict": "keep", "rationale": "a phone number"}"#)).await;
+
+        assert!(dismissed.is_empty());
+        let verification = kept[0].verification.as_ref().unwrap();
+        assert_eq!(verification.verdict, Verdict::Keep);
+        assert_eq!(verification.rationale, "a phone number");
+        assert_eq!(verification.model, "canned");
+    }
+
+    #[tokio::test]
+    async fn drop_verdict_dismisses_the_detection() {
+        let (kept, dismissed) = verify_with(Canned(r#"{"verdict": "drop", "rationale": "a build number"}"#)).await;
+
+        assert!(kept.is_empty());
+        let verification = dismissed[0].verification.as_ref().unwrap();
+        assert_eq!(verification.verdict, Verdict::Drop);
+        assert_eq!(verification.rationale, "a build number");
+    }
+
+    #[tokio::test]
+    async fn backend_error_keeps_the_detection() {
+        let (kept, dismissed) = verify_with(Unreachable).await;
+
+        assert!(dismissed.is_empty());
+        let verification = kept[0].verification.as_ref().unwrap();
+        assert_eq!(verification.verdict, Verdict::Keep);
+        assert!(verification.rationale.contains("connection refused"), "{}", verification.rationale);
+    }
+}
@@ -1,9 +1,13 @@
 pub mod pii_scanner;
 pub mod compliance_enforcer;
 pub mod llm_reasoner;
+pub mod llm_verifier;
+pub mod llm_extractor;
 pub mod chatbot_compliance;
 
 pub use pii_scanner::{Agent, PiiScannerAgent};
 pub use compliance_enforcer::ComplianceEnforcerAgent;
 pub use llm_reasoner::LlmReasonerAgent;
+pub use llm_verifier::LlmVerifierAgent;
+pub use llm_extractor::LlmExtractorAgent;
 pub use chatbot_compliance::ChatbotComplianceAgent; 
\ No newline at end of file
@@ -32,6 +32,10 @@ impl PiiScannerAgent {
             agent_id: "pii-scanner-001".to_string(),
         }
     }
+    
+    pub fn from_model_file(path: &str) -> Result<Self> {
+        Ok(Self::with_classifier(PiiClassifier::load(path)?))
+    }
 }
 
 #[async_trait]
@@ -0,0 +1,503 @@
+use crate::policy::{CompliancePolicy, PolicyAction};
+use crate::types::{ChatMessage, ComplianceResult, GuardrailDecision, PiiDetection, PiiType, Verdict, ViolationType};
+use crate::utils::files;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Used when no `--audit` is given
+pub const DEFAULT_AUDIT_PATH: &str = "data/audit.jsonl";
+
+/// Secret keying the hashes of detected values and of the entry chain
+pub const AUDIT_KEY_ENV: &str = "PII_AUDIT_KEY";
+
+/// `prev_hash` of the first entry
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// What was audited
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEvent {
+    /// One run of the coordinator's scan-and-enforce pipeline
+    PipelineRun,
+    /// A chat message from a user
+    UserMessage,
+    /// A bot reply checked by the outbound guardrail
+    BotReply,
+}
+
+/// One detection as it appears in the audit log: never the raw value
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditDetection {
+    pub pii_type: PiiType,
+    /// `hmac-sha256:<hex>` under `PII_AUDIT_KEY`
+    pub value_hash: String,
+    pub confidence: f32,
+    /// Policy action applied to the value; `allow` for dismissed detections,
+    /// which stay in the text
+    pub action: PolicyAction,
+    /// LLM verification verdict, when the detection was verified
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verdict: Option<Verdict>,
+}
+
+/// Everything an entry's hash covers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub sequence: u64,
+    pub timestamp: DateTime<Utc>,
+    pub event: AuditEvent,
+    pub policy_version: String,
+    /// Agents that handled the text, in pipeline order
+    pub agent_ids: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    pub detections: Vec<AuditDetection>,
+    /// Detections LLM verification dropped, so no action was applied
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dismissed: Vec<AuditDetection>,
+    /// What happened to the text: passed through, redacted, blocked or regenerated
+    pub decision: GuardrailDecision,
+    /// Violations the event raised
+    #[serde(default)]
+    pub violations: Vec<ViolationType>,
+    pub prev_hash: String,
+}
+
+/// One line of the audit log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    #[serde(flatten)]
+    pub record: AuditRecord,
+    /// HMAC-SHA256 of the record's JSON, which includes `prev_hash`, under
+    /// `PII_AUDIT_KEY`
+    pub hash: String,
+}
+
+impl AuditRecord {
+    /// Keyed, so entries cannot be rewritten and re-chained without the key
+    pub fn hash(&self, key: &[u8]) -> Result<String> {
+        let json = serde_json::to_string(self)?;
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts any key length");
+        mac.update(json.as_bytes());
+        Ok(hex::encode(mac.finalize().into_bytes()))
+    }
+}
+
+/// What `AuditLog::verify` found in an intact log
+#[derive(Debug, Clone)]
+pub struct AuditVerification {
+    pub entries: u64,
+    /// Hash of the last entry; keep it elsewhere to detect a truncated log
+    pub head: String,
+}
+
+struct ChainHead {
+    sequence: u64,
+    hash: String,
+}
+
+impl ChainHead {
+    fn genesis() -> Self {
+        Self { sequence: 0, hash: GENESIS_HASH.to_string() }
+    }
+}
+
+/// Append-only JSONL log of compliance decisions. Each entry carries the
+/// hash of the one before it, so editing, removing or reordering entries
+/// breaks the chain from that point on.
+///
+/// Several processes may append to one file: each append takes a file lock
+/// and continues from the last entry on disk.
+pub struct AuditLog {
+    key: Vec<u8>,
+    path: Option<PathBuf>,
+    head: Mutex<ChainHead>,
+}
+
+impl AuditLog {
+    /// Keeps entries in memory only, chained but not persisted
+    pub fn in_memory(key: &[u8]) -> Self {
+        Self {
+            key: key.to_vec(),
+            path: None,
+            head: Mutex::new(ChainHead::genesis()),
+        }
+    }
+
+    /// Opens the log at `path` for appending, continuing its chain. The
+    /// existing entries are not verified here; see `verify`.
+    pub fn open(path: &str, key: &[u8]) -> Result<Self> {
+        if let Some(dir) = Path::new(path).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+        let head = read_head(Path::new(path))?;
+
+        Ok(Self {
+            key: key.to_vec(),
+            path: Some(PathBuf::from(path)),
+            head: Mutex::new(head),
+        })
+    }
+
+    /// The log to attach, keyed from `PII_AUDIT_KEY`: `path`, or
+    /// `DEFAULT_AUDIT_PATH` when only the key is set. Auditing is off when
+    /// neither is given; a path without the key is an error.
+    pub fn open_configured(path: Option<&str>) -> Result<Option<Self>> {
+        if path.is_none() && std::env::var(AUDIT_KEY_ENV).map_or(true, |k| k.is_empty()) {
+            return Ok(None);
+        }
+        Self::open(path.unwrap_or(DEFAULT_AUDIT_PATH), key_from_env()?.as_bytes()).map(Some)
+    }
+
+    /// Appends one decision and returns its entry: a pipeline run without
+    /// `message`, a user message or bot reply with one. Values are hashed and
+    /// actions looked up in `policy`; the raw text is never written.
+    pub fn record(
+        &self,
+        policy: &CompliancePolicy,
+        agent_ids: Vec<String>,
+        message: Option<&ChatMessage>,
+        result: &ComplianceResult,
+        decision: GuardrailDecision,
+        violations: Vec<ViolationType>,
+    ) -> Result<AuditEntry> {
+        let detections = result.detected_pii.iter()
+            .map(|d| self.audit_detection(d, policy.action(&d.pii_type)))
+            .collect();
+        let dismissed = result.dismissed_pii.iter()
+            .map(|d| self.audit_detection(d, PolicyAction::Allow))
+            .collect();
+
+        let event = match message {
+            None => AuditEvent::PipelineRun,
+            Some(message) if message.is_user_message => AuditEvent::UserMessage,
+            Some(_) => AuditEvent::BotReply,
+        };
+
+        let mut head = self.head.lock().unwrap();
+        let _lock = match &self.path {
+            Some(path) => {
+                let lock = files::lock(path)?;
+                *head = read_head(path)?;
+                Some(lock)
+            }
+            None => None,
+        };
+
+        let record = AuditRecord {
+            sequence: head.sequence,
+            timestamp: Utc::now(),
+            event,
+            policy_version: policy.version.clone(),
+            agent_ids,
+            session_id: message.map(|m| m.session_id.clone()),
+            message_id: message.map(|m| m.message_id.clone()),
+            detections,
+            dismissed,
+            decision,
+            violations,
+            prev_hash: head.hash.clone(),
+        };
+        let entry = AuditEntry { hash: record.hash(&self.key)?, record };
+
+        if let Some(path) = &self.path {
+            let mut line = serde_json::to_string(&entry)?;
+            line.push('\n');
+            OpenOptions::new().create(true).append(true).open(path)
+                .and_then(|mut file| file.write_all(line.as_bytes()).and_then(|_| file.sync_data()))
+                .with_context(|| format!("failed to append to audit log {}", path.display()))?;
+        }
+        head.sequence += 1;
+        head.hash = entry.hash.clone();
+
+        Ok(entry)
+    }
+
+    /// Checks every entry's keyed hash and its link to the previous entry,
+    /// failing at the first line that does not hold up
+    pub fn verify(path: &str, key: &[u8]) -> Result<AuditVerification> {
+        let file = File::open(path).with_context(|| format!("failed to read audit log {}", path))?;
+        let mut expected_sequence = 0;
+        let mut prev_hash = GENESIS_HASH.to_string();
+
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line_no = i + 1;
+            let line = line.with_context(|| format!("failed to read audit log {}", path))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let entry: AuditEntry = serde_json::from_str(&line)
+                .with_context(|| format!("line {}: not an audit entry", line_no))?;
+            if entry.record.sequence != expected_sequence {
+                anyhow::bail!(
+                    "line {}: sequence {} where {} was expected; entries were removed or reordered",
+                    line_no, entry.record.sequence, expected_sequence
+                );
+            }
+            if entry.record.prev_hash != prev_hash {
+                anyhow::bail!("line {}: does not link to the previous entry", line_no);
+            }
+            if entry.record.hash(key)? != entry.hash {
+                anyhow::bail!(
+                    "line {}: contents do not match the entry hash; the entry was modified or the key is wrong",
+                    line_no
+                );
+            }
+
+            expected_sequence += 1;
+            prev_hash = entry.hash;
+        }
+
+        Ok(AuditVerification { entries: expected_sequence, head: prev_hash })
+    }
+
+    /// `Redacted` when any detection is rewritten under `policy`, else `Passed`
+    pub fn redaction_decision(policy: &CompliancePolicy, detections: &[PiiDetection]) -> GuardrailDecision {
+        if detections.iter().any(|d| policy.action(&d.pii_type) != PolicyAction::Allow) {
+            GuardrailDecision::Redacted
+        } else {
+            GuardrailDecision::Passed
+        }
+    }
+
+    fn audit_detection(&self, detection: &PiiDetection, action: PolicyAction) -> AuditDetection {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.key).expect("HMAC accepts any key length");
+        mac.update(detection.value.as_bytes());
+
+        AuditDetection {
+            pii_type: detection.pii_type.clone(),
+            value_hash: format!("hmac-sha256:{}", hex::encode(mac.finalize().into_bytes())),
+            confidence: detection.confidence,
+            action,
+            verdict: detection.verification.as_ref().map(|v| v.verdict),
+        }
+    }
+}
+
+/// `PII_AUDIT_KEY`, which keys both value hashes and the chain. Without it
+/// guessable values such as SSNs could be recovered from their hashes, and
+/// a rewritten log could be re-chained.
+pub fn key_from_env() -> Result<String> {
+    std::env::var(AUDIT_KEY_ENV)
+        .ok()
+        .filter(|k| !k.is_empty())
+        .with_context(|| format!("{} must be set to write or verify the audit log", AUDIT_KEY_ENV))
+}
+
+/// Where the chain continues: after the last entry in `path`, read from the
+/// end of the file
+fn read_head(path: &Path) -> Result<ChainHead> {
+    let Some(line) = last_line(path).with_context(|| format!("failed to read audit log {}", path.display()))? else {
+        return Ok(ChainHead::genesis());
+    };
+    let entry: AuditEntry = serde_json::from_str(&line)
+        .with_context(|| format!("last entry of audit log {} is unreadable", path.display()))?;
+
+    Ok(ChainHead { sequence: entry.record.sequence + 1, hash: entry.hash })
+}
+
+/// Last non-blank line of the file, reading backwards in blocks
+fn last_line(path: &Path) -> std::io::Result<Option<String>> {
+    const BLOCK: u64 = 4096;
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    let mut pos = file.metadata()?.len();
+    let mut tail: Vec<u8> = vec![];
+    loop {
+        let end = tail.iter().rposition(|b| !b.is_ascii_whitespace());
+        let start = end.and_then(|end| tail[..end].iter().rposition(|&b| b == b'\n').map(|i| i + 1));
+        if let (Some(end), Some(start)) = (end, start) {
+            return Ok(Some(String::from_utf8_lossy(&tail[start..=end]).into_owned()));
+        }
+        if pos == 0 {
+            return Ok(end.map(|end| String::from_utf8_lossy(&tail[..=end]).into_owned()));
+        }
+
+        let step = BLOCK.min(pos);
+        pos -= step;
+        let mut block = vec![0; step as usize];
+        file.seek(SeekFrom::Start(pos))?;
+        file.read_exact(&mut block)?;
+        block.extend_from_slice(&tail);
+        tail = block;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Verification;
+
+    const KEY: &[u8] = b"test-audit-key";
+
+    fn detection(value: &str) -> PiiDetection {
+        PiiDetection {
+            pii_type: PiiType::Email,
+            confidence: 0.9,
+            start_pos: 0,
+            end_pos: value.len(),
+            value: value.to_string(),
+            card_network: None,
+            verification: None,
+        }
+    }
+
+    fn result(detections: Vec<PiiDetection>) -> ComplianceResult {
+        ComplianceResult {
+            original_text: String::new(),
+            redacted_text: String::new(),
+            detected_pii: detections,
+            compliance_score: 0.0,
+            recommendations: vec![],
+            redaction_map: vec![],
+            dismissed_pii: vec![],
+            cross_message_pii: vec![],
+        }
+    }
+
+    /// Writes a log of `count` entries and returns its lines
+    fn write_log(path: &str, count: usize) -> Vec<String> {
+        let log = AuditLog::open(path, KEY).unwrap();
+        let policy = CompliancePolicy::default();
+        for i in 0..count {
+            let result = result(vec![detection(&format!("user{}@example.com", i))]);
+            log.record(&policy, vec!["pii_detector".into()], None, &result, GuardrailDecision::Redacted, vec![])
+                .unwrap();
+        }
+        std::fs::read_to_string(path).unwrap().lines().map(String::from).collect()
+    }
+
+    fn rewrite(path: &str, lines: &[String]) {
+        std::fs::write(path, lines.join("\n") + "\n").unwrap();
+    }
+
+    fn verify_error(path: &str) -> String {
+        AuditLog::verify(path, KEY).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn intact_chain_verifies() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let path = path.to_str().unwrap();
+        let lines = write_log(path, 4);
+
+        let verification = AuditLog::verify(path, KEY).unwrap();
+        let last: AuditEntry = serde_json::from_str(&lines[3]).unwrap();
+        assert_eq!(verification.entries, 4);
+        assert_eq!(verification.head, last.hash);
+        assert!(!lines.iter().any(|l| l.contains("example.com")));
+    }
+
+    #[test]
+    fn edited_entry_fails_at_its_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let path = path.to_str().unwrap();
+        let mut lines = write_log(path, 4);
+
+        let mut entry: serde_json::Value = serde_json::from_str(&lines[1]).unwrap();
+        entry["decision"] = serde_json::json!("passed");
+        lines[1] = entry.to_string();
+        rewrite(path, &lines);
+
+        let error = verify_error(path);
+        assert!(error.starts_with("line 2:"), "{}", error);
+        assert!(error.contains("modified"), "{}", error);
+    }
+
+    #[test]
+    fn removed_entry_fails_at_the_gap() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let path = path.to_str().unwrap();
+        let mut lines = write_log(path, 4);
+
+        lines.remove(2);
+        rewrite(path, &lines);
+
+        let error = verify_error(path);
+        assert!(error.starts_with("line 3:"), "{}", error);
+        assert!(error.contains("removed or reordered"), "{}", error);
+    }
+
+    #[test]
+    fn reordered_entries_fail_at_the_first_swap() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let path = path.to_str().unwrap();
+        let mut lines = write_log(path, 4);
+
+        lines.swap(1, 2);
+        rewrite(path, &lines);
+
+        let error = verify_error(path);
+        assert!(error.starts_with("line 2:"), "{}", error);
+        assert!(error.contains("removed or reordered"), "{}", error);
+    }
+
+    #[test]
+    fn chain_does_not_verify_under_another_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let path = path.to_str().unwrap();
+        write_log(path, 2);
+
+        let error = AuditLog::verify(path, b"another-key").unwrap_err().to_string();
+        assert!(error.starts_with("line 1:"), "{}", error);
+    }
+
+    #[test]
+    fn logs_sharing_a_file_append_to_one_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let path = path.to_str().unwrap();
+        let server = AuditLog::open(path, KEY).unwrap();
+        let chatbot = AuditLog::open(path, KEY).unwrap();
+        let policy = CompliancePolicy::default();
+
+        for log in [&server, &chatbot, &server, &chatbot] {
+            log.record(&policy, vec![], None, &result(vec![]), GuardrailDecision::Passed, vec![]).unwrap();
+        }
+
+        assert_eq!(AuditLog::verify(path, KEY).unwrap().entries, 4);
+    }
+
+    #[test]
+    fn dismissed_detections_are_recorded_with_their_verdict() {
+        let log = AuditLog::in_memory(KEY);
+        let mut dismissed = detection("v1.2.3.4");
+        dismissed.verification = Some(Verification {
+            verdict: Verdict::Drop,
+            rationale: "a version number".into(),
+            model: "test".into(),
+        });
+        let mut result = result(vec![detection("a@example.com")]);
+        result.dismissed_pii.push(dismissed);
+
+        let entry = log
+            .record(&CompliancePolicy::default(), vec![], None, &result, GuardrailDecision::Redacted, vec![])
+            .unwrap();
+
+        assert_eq!(entry.record.detections.len(), 1);
+        assert_eq!(entry.record.dismissed.len(), 1);
+        assert_eq!(entry.record.dismissed[0].verdict, Some(Verdict::Drop));
+        assert_eq!(entry.record.dismissed[0].action, PolicyAction::Allow);
+        assert!(entry.record.dismissed[0].value_hash.starts_with("hmac-sha256:"));
+    }
+}
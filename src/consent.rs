@@ -0,0 +1,259 @@
+use crate::types::PiiType;
+use crate::utils::files;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// Used when no `--consent` is given
+pub const DEFAULT_CONSENT_PATH: &str = "data/consent.json";
+
+/// Purpose checked for chat messages when the session declares none
+pub const DEFAULT_CONSENT_PURPOSE: &str = "chat";
+
+/// Shared secret callers of the consent HTTP endpoints must present; unset
+/// disables the endpoints
+pub const CONSENT_ACCESS_KEY_ENV: &str = "PII_CONSENT_ACCESS_KEY";
+
+const CONSENT_FORMAT_VERSION: u32 = 1;
+
+/// A user's permission to share some PII categories for one purpose
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsentGrant {
+    pub user_id: String,
+    pub categories: Vec<PiiType>,
+    pub purpose: String,
+    pub granted_at: DateTime<Utc>,
+    /// Open-ended when unset
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Revoked grants are kept as a record of what was consented to when
+    #[serde(default)]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ConsentGrant {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+
+    pub fn covers(&self, pii_type: &PiiType, purpose: &str, now: DateTime<Utc>) -> bool {
+        self.is_active(now) && self.purpose == purpose && self.categories.contains(pii_type)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ConsentFile {
+    format_version: u32,
+    grants: Vec<ConsentGrant>,
+}
+
+/// Grants as last read from, or written to, the registry file
+#[derive(Default)]
+struct LoadedGrants {
+    grants: Vec<ConsentGrant>,
+    modified: Option<SystemTime>,
+}
+
+/// Consent grants keyed by `ChatMessage.user_id`, saved to a JSON file on
+/// every change when opened from a path.
+///
+/// Several processes may share the file, e.g. the web server recording
+/// grants and a chatbot checking them: lookups re-read it whenever it has
+/// changed, and changes are made under a file lock against its latest
+/// contents.
+pub struct ConsentRegistry {
+    path: Option<PathBuf>,
+    loaded: Mutex<LoadedGrants>,
+}
+
+impl ConsentRegistry {
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            loaded: Mutex::new(LoadedGrants::default()),
+        }
+    }
+
+    /// Opens the registry at `path`, creating it on first write
+    pub fn open(path: &str) -> Result<Self> {
+        let path = PathBuf::from(path);
+        let loaded = read_grants(&path)?;
+
+        Ok(Self {
+            path: Some(path),
+            loaded: Mutex::new(loaded),
+        })
+    }
+
+    /// Opens `path`, or `DEFAULT_CONSENT_PATH` when none is given
+    pub fn open_or_default(path: Option<&str>) -> Result<Self> {
+        Self::open(path.unwrap_or(DEFAULT_CONSENT_PATH))
+    }
+
+    /// Records consent for `categories` under `purpose`
+    pub fn grant(
+        &self,
+        user_id: &str,
+        categories: Vec<PiiType>,
+        purpose: &str,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<ConsentGrant> {
+        if user_id.trim().is_empty() || purpose.trim().is_empty() || categories.is_empty() {
+            anyhow::bail!("a consent grant needs a user_id, a purpose and at least one category");
+        }
+        let now = Utc::now();
+        if expires_at.is_some_and(|expires_at| expires_at <= now) {
+            anyhow::bail!("consent expiry must be in the future");
+        }
+
+        let grant = ConsentGrant {
+            user_id: user_id.to_string(),
+            categories,
+            purpose: purpose.to_string(),
+            granted_at: now,
+            expires_at,
+            revoked_at: None,
+        };
+
+        self.update(|grants| grants.push(grant.clone()))?;
+        Ok(grant)
+    }
+
+    /// Revokes the user's active grants, only those for `purpose` if given.
+    /// Returns how many were revoked.
+    pub fn revoke(&self, user_id: &str, purpose: Option<&str>) -> Result<usize> {
+        let now = Utc::now();
+        let mut revoked = 0;
+        self.update(|grants| {
+            for grant in grants.iter_mut() {
+                if grant.user_id == user_id && purpose.is_none_or(|p| grant.purpose == p) && grant.is_active(now) {
+                    grant.revoked_at = Some(now);
+                    revoked += 1;
+                }
+            }
+        })?;
+        Ok(revoked)
+    }
+
+    pub fn has_consent(&self, user_id: &str, pii_type: &PiiType, purpose: &str) -> bool {
+        let now = Utc::now();
+        self.current().grants
+            .iter()
+            .any(|grant| grant.user_id == user_id && grant.covers(pii_type, purpose, now))
+    }
+
+    /// Every grant recorded for the user, including expired and revoked ones
+    pub fn grants(&self, user_id: &str) -> Vec<ConsentGrant> {
+        self.current().grants
+            .iter()
+            .filter(|grant| grant.user_id == user_id)
+            .cloned()
+            .collect()
+    }
+
+    /// The loaded grants, re-read first if the file changed since. A file
+    /// that cannot be read is logged and the last grants read are used.
+    fn current(&self) -> std::sync::MutexGuard<'_, LoadedGrants> {
+        let mut loaded = self.loaded.lock().unwrap();
+        if let Some(path) = &self.path {
+            if modified(path) != loaded.modified {
+                match read_grants(path) {
+                    Ok(fresh) => *loaded = fresh,
+                    Err(e) => tracing::error!("failed to reload consent registry: {:#}", e),
+                }
+            }
+        }
+        loaded
+    }
+
+    /// Applies `change` to the latest grants and saves them, holding the
+    /// file lock so concurrent writers do not drop each other's changes
+    fn update(&self, change: impl FnOnce(&mut Vec<ConsentGrant>)) -> Result<()> {
+        let mut loaded = self.loaded.lock().unwrap();
+        let Some(path) = &self.path else {
+            change(&mut loaded.grants);
+            return Ok(());
+        };
+
+        let _lock = files::lock(path)?;
+        let mut latest = read_grants(path)?;
+        change(&mut latest.grants);
+
+        let file = ConsentFile { format_version: CONSENT_FORMAT_VERSION, grants: latest.grants };
+        files::write_atomic(path, &serde_json::to_string_pretty(&file)?)?;
+        *loaded = LoadedGrants { grants: file.grants, modified: modified(path) };
+        Ok(())
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Grants stored at `path`, none if the registry has not been written yet
+fn read_grants(path: &Path) -> Result<LoadedGrants> {
+    let modified = modified(path);
+    if modified.is_none() {
+        return Ok(LoadedGrants::default());
+    }
+
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read consent registry {}", path.display()))?;
+    let file: ConsentFile = serde_json::from_str(&contents)
+        .with_context(|| format!("{} is not a consent registry", path.display()))?;
+    if file.format_version != CONSENT_FORMAT_VERSION {
+        anyhow::bail!(
+            "consent registry {} has format version {}, expected {}",
+            path.display(), file.format_version, CONSENT_FORMAT_VERSION
+        );
+    }
+    Ok(LoadedGrants { grants: file.grants, modified })
+}
+
+impl Default for ConsentRegistry {
+    fn default() -> Self {
+        Self::in_memory()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registries_sharing_a_file_see_each_others_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("consent.json");
+        let path = path.to_str().unwrap();
+        let server = ConsentRegistry::open(path).unwrap();
+        let chatbot = ConsentRegistry::open(path).unwrap();
+
+        assert!(!chatbot.has_consent("u1", &PiiType::Email, "chat"));
+        server.grant("u1", vec![PiiType::Email], "chat", None).unwrap();
+        assert!(chatbot.has_consent("u1", &PiiType::Email, "chat"));
+
+        // Writes start from the file, not from each registry's stale copy
+        chatbot.grant("u2", vec![PiiType::PhoneNumber], "chat", None).unwrap();
+        assert_eq!(server.revoke("u1", None).unwrap(), 1);
+        assert!(!chatbot.has_consent("u1", &PiiType::Email, "chat"));
+        assert!(server.has_consent("u2", &PiiType::PhoneNumber, "chat"));
+
+        let reopened = ConsentRegistry::open(path).unwrap();
+        assert_eq!(reopened.grants("u1").len(), 1);
+        assert_eq!(reopened.grants("u2").len(), 1);
+    }
+
+    #[test]
+    fn in_memory_registry_records_and_revokes() {
+        let registry = ConsentRegistry::in_memory();
+        registry.grant("u1", vec![PiiType::Email], "chat", None).unwrap();
+
+        assert!(registry.has_consent("u1", &PiiType::Email, "chat"));
+        assert!(!registry.has_consent("u1", &PiiType::Email, "support"));
+        assert_eq!(registry.revoke("u1", Some("chat")).unwrap(), 1);
+        assert!(!registry.has_consent("u1", &PiiType::Email, "chat"));
+    }
+}
@@ -1,5 +1,7 @@
-use crate::agents::{Agent, PiiScannerAgent, ComplianceEnforcerAgent, LlmReasonerAgent};
-use crate::types::{AgentContext, AgentMessage, ComplianceResult, MessageType, PiiDetection};
+use crate::audit::AuditLog;
+use crate::agents::{Agent, PiiScannerAgent, ComplianceEnforcerAgent, LlmReasonerAgent, LlmExtractorAgent, LlmVerifierAgent};
+use crate::models::resolve_spans;
+use crate::types::{AgentContext, AgentMessage, ComplianceResult, MessageType, PiiDetection, RedactionSpan};
 use anyhow::Result;
 use serde_json::json;
 use std::sync::Arc;
@@ -8,6 +10,9 @@ use tokio::sync::Mutex;
 pub struct AgentCoordinator {
     context: Arc<Mutex<AgentContext>>,
     coordinator_id: String,
+    extractor: Option<LlmExtractorAgent>,
+    verifier: Option<LlmVerifierAgent>,
+    audit: Option<Arc<AuditLog>>,
 }
 
 impl AgentCoordinator {
@@ -15,9 +20,30 @@ impl AgentCoordinator {
         Self {
             context: Arc::new(Mutex::new(AgentContext::default())),
             coordinator_id: "coordinator-001".to_string(),
+            extractor: None,
+            verifier: None,
+            audit: None,
         }
     }
     
+    /// Adds LLM extraction of names, addresses and the like alongside the scanner
+    pub fn with_extractor(mut self, extractor: Option<LlmExtractorAgent>) -> Self {
+        self.extractor = extractor;
+        self
+    }
+    
+    /// Adds an LLM verification pass between scanning and enforcement
+    pub fn with_verifier(mut self, verifier: Option<LlmVerifierAgent>) -> Self {
+        self.verifier = verifier;
+        self
+    }
+    
+    /// Records every pipeline run in `audit`
+    pub fn with_audit(mut self, audit: Option<Arc<AuditLog>>) -> Self {
+        self.audit = audit;
+        self
+    }
+    
     pub async fn run_compliance_pipeline(
         &self,
         text: &str,
@@ -41,9 +67,46 @@ impl AgentCoordinator {
         let scan_result = scanner.process(scan_message, &mut context).await?;
         
         // Extract detections from scan result
-        let detections: Vec<PiiDetection> = serde_json::from_value(
+        let mut detections: Vec<PiiDetection> = serde_json::from_value(
             scan_result.payload["detections"].clone()
         ).unwrap_or_default();
+        let mut dismissed_pii: Vec<PiiDetection> = vec![];
+        
+        // Optional: merge in what the LLM finds that patterns cannot
+        if let Some(extractor) = &self.extractor {
+            let extraction_message = AgentMessage {
+                agent_id: self.coordinator_id.clone(),
+                message_type: MessageType::PiiExtractionRequest,
+                payload: json!({
+                    "text": text,
+                }),
+                timestamp: chrono::Utc::now(),
+            };
+            
+            let extraction_result = extractor.process(extraction_message, &mut context).await?;
+            let extracted: Vec<PiiDetection> = serde_json::from_value(
+                extraction_result.payload["detections"].clone()
+            ).unwrap_or_default();
+            detections.extend(extracted);
+            detections = resolve_spans(text, detections);
+        }
+        
+        // Optional: let the LLM weed out ambiguous matches before enforcement
+        if let Some(verifier) = &self.verifier {
+            let verification_message = AgentMessage {
+                agent_id: self.coordinator_id.clone(),
+                message_type: MessageType::PiiVerificationRequest,
+                payload: json!({
+                    "text": text,
+                    "detections": detections,
+                }),
+                timestamp: chrono::Utc::now(),
+            };
+            
+            let verification_result = verifier.process(verification_message, &mut context).await?;
+            detections = serde_json::from_value(verification_result.payload["detections"].clone()).unwrap_or_default();
+            dismissed_pii = serde_json::from_value(verification_result.payload["dismissed"].clone()).unwrap_or_default();
+        }
         
         // Step 2: Apply compliance enforcement
         let enforcement_message = AgentMessage {
@@ -70,12 +133,19 @@ impl AgentCoordinator {
             enforcement_result.payload["detected_pii"].clone()
         ).unwrap_or_default();
         
+        let redaction_map: Vec<RedactionSpan> = serde_json::from_value(
+            enforcement_result.payload["redaction_map"].clone()
+        ).unwrap_or_default();
+        
         let compliance_result = ComplianceResult {
             original_text: text.to_string(),
             redacted_text: redacted_text.to_string(),
             detected_pii: final_detections,
             compliance_score,
             recommendations,
+            redaction_map,
+            dismissed_pii,
+            cross_message_pii: vec![],
         };
         
         // Step 3: Get LLM reasoning
@@ -95,6 +165,19 @@ impl AgentCoordinator {
         let llm_explanation = reasoning_result.payload["llm_explanation"].as_str().unwrap_or("");
         println!("🤖 LLM Reasoning: {}", llm_explanation);
         
+        // Step 4: Leave a tamper-evident record of the decision
+        if let Some(audit) = &self.audit {
+            let mut agent_ids = vec![self.coordinator_id.clone(), scanner.agent_id().to_string()];
+            agent_ids.extend(self.extractor.as_ref().map(|e| e.agent_id().to_string()));
+            agent_ids.extend(self.verifier.as_ref().map(|v| v.agent_id().to_string()));
+            agent_ids.push(enforcer.agent_id().to_string());
+            agent_ids.push(reasoner.agent_id().to_string());
+            
+            let policy = enforcer.policy();
+            let decision = AuditLog::redaction_decision(policy, &compliance_result.detected_pii);
+            audit.record(policy, agent_ids, None, &compliance_result, decision, vec![])?;
+        }
+        
         Ok(compliance_result)
     }
     
@@ -124,4 +207,10 @@ impl AgentCoordinator {
         
         Ok(results)
     }
-} 
\ No newline at end of file
+}
+
+impl Default for AgentCoordinator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,116 @@
+use crate::policy::{CompliancePolicy, HashConfig, PolicyAction};
+use crate::redaction::normalize;
+use crate::types::PiiType;
+use anyhow::Result;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Secret keying the `hash` action
+pub const HASH_SECRET_ENV: &str = "PII_HASH_SECRET";
+
+/// Keyed HMAC-SHA256 join keys for the `hash` action.
+///
+/// A join key is the first `length` hex characters of
+/// `HMAC-SHA256(secret, value)`, where `value` is normalized first when the
+/// policy asks (lowercased emails, digits-only phone numbers), so other
+/// systems holding the secret can compute matching keys.
+pub struct KeyedHasher {
+    secret: Vec<u8>,
+    config: HashConfig,
+}
+
+impl KeyedHasher {
+    pub fn new(secret: &[u8], config: HashConfig) -> Self {
+        Self {
+            secret: secret.to_vec(),
+            config,
+        }
+    }
+
+    /// Keyed from `PII_HASH_SECRET` with the policy's `[hashing]` settings,
+    /// failing if the policy hashes but no secret is configured
+    pub fn for_policy(policy: &CompliancePolicy) -> Result<Option<Self>> {
+        let secret = std::env::var(HASH_SECRET_ENV).ok().filter(|s| !s.is_empty());
+        match secret {
+            Some(secret) => Ok(Some(Self::new(secret.as_bytes(), policy.hashing.clone()))),
+            None if policy.uses_action(PolicyAction::Hash) => anyhow::bail!(
+                "policy {} hashes values but {} is not set",
+                policy.version, HASH_SECRET_ENV
+            ),
+            None => Ok(None),
+        }
+    }
+
+    pub fn hash(&self, pii_type: &PiiType, value: &str) -> String {
+        let input = if self.config.normalize {
+            normalize(pii_type, value)
+        } else {
+            value.to_string()
+        };
+
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.secret).expect("HMAC accepts any key length");
+        mac.update(input.as_bytes());
+        let digest = hex::encode(mac.finalize().into_bytes());
+
+        digest[..self.config.length.min(digest.len())].to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hasher(secret: &[u8], length: usize, normalize: bool) -> KeyedHasher {
+        KeyedHasher::new(secret, HashConfig { length, normalize })
+    }
+
+    #[test]
+    fn normalization_joins_spellings_of_the_same_value() {
+        let normalizing = hasher(b"secret", 16, true);
+        assert_eq!(
+            normalizing.hash(&PiiType::Email, "John@Example.com"),
+            normalizing.hash(&PiiType::Email, "john@example.com")
+        );
+        assert_eq!(
+            normalizing.hash(&PiiType::PhoneNumber, "(555) 123-4567"),
+            normalizing.hash(&PiiType::PhoneNumber, "555.123.4567")
+        );
+
+        let verbatim = hasher(b"secret", 16, false);
+        assert_ne!(
+            verbatim.hash(&PiiType::Email, "John@Example.com"),
+            verbatim.hash(&PiiType::Email, "john@example.com")
+        );
+    }
+
+    #[test]
+    fn join_key_is_a_prefix_of_the_full_digest() {
+        let full = hasher(b"secret", 64, true).hash(&PiiType::Email, "a@b.co");
+        let short = hasher(b"secret", 8, true).hash(&PiiType::Email, "a@b.co");
+
+        assert_eq!(full.len(), 64);
+        assert!(full.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(short, full[..8]);
+    }
+
+    #[test]
+    fn join_key_depends_on_the_secret() {
+        assert_ne!(
+            hasher(b"secret", 16, true).hash(&PiiType::Email, "a@b.co"),
+            hasher(b"other", 16, true).hash(&PiiType::Email, "a@b.co")
+        );
+    }
+
+    #[test]
+    fn policy_that_hashes_needs_a_secret() {
+        // The only test that reads this variable
+        std::env::remove_var(HASH_SECRET_ENV);
+
+        let mut policy = CompliancePolicy::default();
+        assert!(KeyedHasher::for_policy(&policy).unwrap().is_none());
+
+        policy.rules.get_mut(&PiiType::Email).unwrap().action = PolicyAction::Hash;
+        let error = KeyedHasher::for_policy(&policy).err().unwrap();
+        assert!(error.to_string().contains(HASH_SECRET_ENV), "{}", error);
+    }
+}
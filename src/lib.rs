@@ -1,8 +1,20 @@
+pub mod access;
 pub mod agents;
+pub mod audit;
+pub mod consent;
 pub mod coordinator;
+pub mod generalization;
+pub mod hashing;
+pub mod llm;
 pub mod models;
+pub mod policy;
+pub mod pseudonym;
+pub mod redaction;
+pub mod session_store;
+pub mod synthetic;
 pub mod types;
 pub mod utils;
+pub mod vault;
 pub mod web_server;
 
 pub use types::*; 
\ No newline at end of file
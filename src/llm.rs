@@ -0,0 +1,461 @@
+use crate::types::PiiType;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Picked up automatically by the CLI and web server when present
+pub const DEFAULT_LLM_CONFIG_PATH: &str = "config/llm.toml";
+
+/// Bearer token for the endpoint, if it needs one
+pub const LLM_API_KEY_ENV: &str = "LLM_API_KEY";
+
+/// Upper bound on `[verification] context_chars`, to keep prompts small
+pub const MAX_CONTEXT_CHARS: usize = 2000;
+
+/// A chat model the agents can ask for text
+#[async_trait]
+pub trait LlmBackend: Send + Sync {
+    /// Sends one system + user exchange and returns the reply text
+    async fn complete(&self, system: &str, user: &str) -> Result<String>;
+
+    /// Model name, recorded alongside LLM-made decisions
+    fn model(&self) -> &str;
+}
+
+/// Prompts sent to the backend. `{name}` placeholders are filled by `render`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptTemplates {
+    #[serde(default = "default_system_prompt")]
+    pub system: String,
+    /// Placeholders: `{pii_count}`, `{pii_types}`, `{compliance_score}`, `{redacted_text}`
+    #[serde(default = "default_explanation_prompt")]
+    pub explanation: String,
+    /// Placeholders: `{pii_type}`, `{value}`, `{context}`. Must ask for
+    /// `{"verdict": "keep" | "drop", "rationale": "..."}`
+    #[serde(default = "default_verification_prompt")]
+    pub verification: String,
+    /// Placeholders: `{types}`, `{text}`. Must ask for
+    /// `{"entities": [{"type": "...", "text": "..."}]}`
+    #[serde(default = "default_extraction_prompt")]
+    pub extraction: String,
+    /// Placeholder: `{reply}`, a chatbot reply with PII already replaced by tags
+    #[serde(default = "default_regeneration_prompt")]
+    pub regeneration: String,
+}
+
+fn default_system_prompt() -> String {
+    "You are a data protection assistant. You review text for personally identifiable \
+     information (PII) and explain compliance risks concisely. Never repeat PII values."
+        .to_string()
+}
+
+fn default_explanation_prompt() -> String {
+    "A scan found {pii_count} PII item(s) of these types: {pii_types}. The compliance score is \
+     {compliance_score}%. The redacted text is:\n\n{redacted_text}\n\nIn two sentences, explain \
+     the risk and what the user should do."
+        .to_string()
+}
+
+fn default_verification_prompt() -> String {
+    "A pattern matcher flagged \"{value}\" as {pii_type} in this excerpt:\n\n{context}\n\n\
+     Is it really personal data of that type, or something else such as a version number, order \
+     ID or timestamp? Answer only with JSON: {\"verdict\": \"keep\" or \"drop\", \"rationale\": \
+     \"one sentence\"}"
+        .to_string()
+}
+
+fn default_extraction_prompt() -> String {
+    "Find every {types} in the text below. Answer only with JSON: {\"entities\": [{\"type\": \
+     one of {types}, \"text\": the exact substring}]}. Use an empty list when there are \
+     none.\n\nText:\n{text}"
+        .to_string()
+}
+
+fn default_regeneration_prompt() -> String {
+    "Rewrite this chatbot reply so it keeps its meaning but contains no personal data. Tags in \
+     square brackets mark removed values: do not fill them in or guess them; refer to the \
+     information generically instead. Answer with the rewritten reply only.\n\n{reply}"
+        .to_string()
+}
+
+impl Default for PromptTemplates {
+    fn default() -> Self {
+        Self {
+            system: default_system_prompt(),
+            explanation: default_explanation_prompt(),
+            verification: default_verification_prompt(),
+            extraction: default_extraction_prompt(),
+            regeneration: default_regeneration_prompt(),
+        }
+    }
+}
+
+/// Second-pass LLM check of ambiguous detections in the compliance pipeline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerificationConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Detections below this confidence are verified
+    #[serde(default = "default_verify_below")]
+    pub below_confidence: f32,
+    /// Characters of surrounding text sent on each side of the value,
+    /// 1 to `MAX_CONTEXT_CHARS`
+    #[serde(default = "default_context_chars")]
+    pub context_chars: usize,
+}
+
+fn default_verify_below() -> f32 {
+    0.95
+}
+
+fn default_context_chars() -> usize {
+    60
+}
+
+impl Default for VerificationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            below_confidence: default_verify_below(),
+            context_chars: default_context_chars(),
+        }
+    }
+}
+
+/// LLM extraction of the free-form PII the pattern scanner cannot find
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtractionConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Types the LLM is asked for; anything else it returns is ignored
+    #[serde(default = "default_extraction_types")]
+    pub types: Vec<PiiType>,
+    /// Confidence given to LLM-extracted spans
+    #[serde(default = "default_extraction_confidence")]
+    pub confidence: f32,
+}
+
+fn default_extraction_types() -> Vec<PiiType> {
+    vec![PiiType::Name, PiiType::Address, PiiType::DateOfBirth]
+}
+
+fn default_extraction_confidence() -> f32 {
+    0.85
+}
+
+impl Default for ExtractionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            types: default_extraction_types(),
+            confidence: default_extraction_confidence(),
+        }
+    }
+}
+
+/// Fills `{name}` placeholders in `template`
+pub fn render(template: &str, values: &[(&str, String)]) -> String {
+    values.iter().fold(template.to_string(), |text, (name, value)| {
+        text.replace(&format!("{{{}}}", name), value)
+    })
+}
+
+/// Reads the JSON object in a model reply. Models often wrap JSON in prose or
+/// code fences, so the outermost `{...}` is parsed.
+pub fn parse_json_reply<T: DeserializeOwned>(reply: &str) -> Result<T> {
+    let json = reply.find('{')
+        .zip(reply.rfind('}'))
+        .filter(|(start, end)| start < end)
+        .map(|(start, end)| &reply[start..=end])
+        .ok_or_else(|| anyhow::anyhow!("no JSON object in reply {:?}", reply))?;
+    serde_json::from_str(json).map_err(|e| anyhow::anyhow!("unreadable reply {:?}: {}", json, e))
+}
+
+/// Connection settings for an OpenAI-compatible chat-completions server
+/// (NIM, vLLM, llama.cpp server, Ollama, ...)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmConfig {
+    /// API root including the version, e.g. "http://localhost:11434/v1"
+    pub base_url: String,
+    pub model: String,
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(default)]
+    pub temperature: f32,
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens: u32,
+    #[serde(default)]
+    pub prompts: PromptTemplates,
+    #[serde(default)]
+    pub verification: VerificationConfig,
+    #[serde(default)]
+    pub extraction: ExtractionConfig,
+}
+
+fn default_timeout_secs() -> u64 {
+    10
+}
+
+fn default_max_tokens() -> u32 {
+    256
+}
+
+impl LlmConfig {
+    pub fn load(path: &str) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read LLM config {}", path))?;
+        let config: Self = toml::from_str(&contents)
+            .with_context(|| format!("{} is not a valid LLM config", path))?;
+
+        if config.base_url.trim().is_empty() || config.model.trim().is_empty() {
+            anyhow::bail!("{}: base_url and model must not be empty", path);
+        }
+        if !(0.0..=1.0).contains(&config.extraction.confidence) {
+            anyhow::bail!("{}: extraction.confidence must be between 0 and 1", path);
+        }
+        if !(0.0..=1.0).contains(&config.verification.below_confidence) {
+            anyhow::bail!("{}: verification.below_confidence must be between 0 and 1", path);
+        }
+        if !(1..=MAX_CONTEXT_CHARS).contains(&config.verification.context_chars) {
+            anyhow::bail!("{}: verification.context_chars must be between 1 and {}", path, MAX_CONTEXT_CHARS);
+        }
+        Ok(config)
+    }
+
+    /// Loads `path` if given, otherwise `DEFAULT_LLM_CONFIG_PATH` when it
+    /// exists. `None` means no LLM: agents use their template text.
+    pub fn load_if_present(path: Option<&str>) -> Result<Option<Self>> {
+        match path {
+            Some(path) => Self::load(path).map(Some),
+            None if Path::new(DEFAULT_LLM_CONFIG_PATH).exists() => Self::load(DEFAULT_LLM_CONFIG_PATH).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Everything the agents need from the LLM config. Without a config there
+/// is no backend, and agents use their template text.
+#[derive(Clone, Default)]
+pub struct LlmSetup {
+    pub backend: Option<Arc<dyn LlmBackend>>,
+    pub prompts: PromptTemplates,
+    pub verification: VerificationConfig,
+    pub extraction: ExtractionConfig,
+}
+
+impl LlmSetup {
+    pub fn load(path: Option<&str>) -> Result<Self> {
+        match LlmConfig::load_if_present(path)? {
+            Some(config) => Ok(Self {
+                prompts: config.prompts.clone(),
+                verification: config.verification.clone(),
+                extraction: config.extraction.clone(),
+                backend: Some(Arc::new(OpenAiCompatibleBackend::new(config)?)),
+            }),
+            None => Ok(Self::default()),
+        }
+    }
+}
+
+/// `LlmBackend` for the OpenAI chat-completions API
+pub struct OpenAiCompatibleBackend {
+    client: reqwest::Client,
+    config: LlmConfig,
+    api_key: Option<String>,
+}
+
+impl OpenAiCompatibleBackend {
+    pub fn new(config: LlmConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()
+            .context("failed to build HTTP client")?;
+        let api_key = std::env::var(LLM_API_KEY_ENV).ok().filter(|k| !k.is_empty());
+
+        Ok(Self { client, config, api_key })
+    }
+
+    pub fn config(&self) -> &LlmConfig {
+        &self.config
+    }
+}
+
+#[derive(Deserialize)]
+struct ChatCompletion {
+    choices: Vec<ChatChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: ChatReply,
+}
+
+#[derive(Deserialize)]
+struct ChatReply {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+#[async_trait]
+impl LlmBackend for OpenAiCompatibleBackend {
+    async fn complete(&self, system: &str, user: &str) -> Result<String> {
+        let url = format!("{}/chat/completions", self.config.base_url.trim_end_matches('/'));
+        let body = json!({
+            "model": self.config.model,
+            "messages": [
+                { "role": "system", "content": system },
+                { "role": "user", "content": user },
+            ],
+            "temperature": self.config.temperature,
+            "max_tokens": self.config.max_tokens,
+        });
+
+        let mut request = self.client.post(&url).json(&body);
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        let response = request.send().await
+            .with_context(|| format!("LLM endpoint {} unreachable", url))?
+            .error_for_status()
+            .with_context(|| format!("LLM endpoint {} returned an error", url))?;
+        let completion: ChatCompletion = response.json().await
+            .with_context(|| format!("LLM endpoint {} returned an unexpected body", url))?;
+
+        completion.choices.into_iter()
+            .next()
+            .and_then(|choice| choice.message.content)
+            .map(|content| content.trim().to_string())
+            .filter(|content| !content.is_empty())
+            .context("LLM returned an empty completion")
+    }
+
+    fn model(&self) -> &str {
+        &self.config.model
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::llm_reasoner::LlmReasonerAgent;
+    use crate::types::ComplianceResult;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serves `body` as the answer to one request, or never answers when `None`
+    async fn stub(body: Option<&'static str>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text[..end].lines()
+                        .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + length || n == 0 {
+                        break;
+                    }
+                }
+            }
+            match body {
+                Some(body) => {
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                        body.len(), body
+                    );
+                    socket.write_all(response.as_bytes()).await.unwrap();
+                }
+                None => tokio::time::sleep(Duration::from_secs(30)).await,
+            }
+        });
+        format!("http://{}/v1", addr)
+    }
+
+    fn backend(base_url: String) -> OpenAiCompatibleBackend {
+        let config: LlmConfig = toml::from_str(&format!(
+            "base_url = \"{}\"\nmodel = \"stub\"\ntimeout_secs = 1",
+            base_url
+        )).unwrap();
+        OpenAiCompatibleBackend::new(config).unwrap()
+    }
+
+    #[test]
+    fn parse_json_reply_reads_the_object_inside_prose_and_fences() {
+        #[derive(Deserialize)]
+        struct Reply {
+            verdict: String,
+        }
+
+        let reply: Reply = parse_json_reply("Here you go:\n```json\n{\"verdict\": \"keep\"}\n```").unwrap();
+        assert_eq!(reply.verdict, "keep");
+        assert!(parse_json_reply::<Reply>("no json here").is_err());
+        assert!(parse_json_reply::<Reply>("{\"other\": 1}").is_err());
+    }
+
+    #[test]
+    fn load_range_checks_verification() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("llm.toml");
+        let path = path.to_str().unwrap();
+        let load = |verification: &str| {
+            std::fs::write(path, format!("base_url = \"http://localhost\"\nmodel = \"m\"\n[verification]\n{}", verification)).unwrap();
+            LlmConfig::load(path)
+        };
+
+        assert!(load("below_confidence = 0.9\ncontext_chars = 80").is_ok());
+        for bad in ["below_confidence = 1.5", "below_confidence = -0.1", "context_chars = 0", "context_chars = 100000"] {
+            let error = load(bad).unwrap_err();
+            assert!(error.to_string().contains("verification."), "{}: {}", bad, error);
+        }
+    }
+
+    #[tokio::test]
+    async fn complete_returns_the_trimmed_reply() {
+        let url = stub(Some(r#"{"choices": [{"message": {"role": "assistant", "content": "  Risky.\n"}}]}"#)).await;
+        assert_eq!(backend(url).complete("system", "user").await.unwrap(), "Risky.");
+    }
+
+    #[tokio::test]
+    async fn complete_rejects_a_malformed_body() {
+        let url = stub(Some(r#"{"unexpected": true}"#)).await;
+        let error = backend(url).complete("system", "user").await.unwrap_err();
+        assert!(format!("{:#}", error).contains("unexpected body"), "{:#}", error);
+
+        let url = stub(Some(r#"{"choices": []}"#)).await;
+        let error = backend(url).complete("system", "user").await.unwrap_err();
+        assert!(format!("{:#}", error).contains("empty completion"), "{:#}", error);
+    }
+
+    #[tokio::test]
+    async fn complete_times_out() {
+        let url = stub(None).await;
+        let error = backend(url).complete("system", "user").await.unwrap_err();
+        assert!(format!("{:#}", error).contains("unreachable"), "{:#}", error);
+    }
+
+    #[tokio::test]
+    async fn reasoner_falls_back_to_the_template_when_unreachable() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/v1", listener.local_addr().unwrap());
+        drop(listener);
+
+        let backend: Arc<dyn LlmBackend> = Arc::new(backend(url));
+        let reasoner = LlmReasonerAgent::new().with_backend(Some(backend), PromptTemplates::default());
+        let (explanation, source) = reasoner.explain(&ComplianceResult::default()).await;
+
+        assert_eq!(source, "template");
+        assert!(!explanation.is_empty());
+    }
+}
@@ -1,9 +1,22 @@
 use clap::{Parser, Subcommand};
+use std::sync::Arc;
 use pii_compliance_agent::{
-    agents::{pii_scanner::PiiScannerAgent, compliance_enforcer::ComplianceEnforcerAgent, llm_reasoner::LlmReasonerAgent, chatbot_compliance::ChatbotComplianceAgent},
+    audit::{self, AuditLog, DEFAULT_AUDIT_PATH},
+    agents::{LlmExtractorAgent, LlmVerifierAgent, pii_scanner::PiiScannerAgent, compliance_enforcer::ComplianceEnforcerAgent, llm_reasoner::LlmReasonerAgent, chatbot_compliance::ChatbotComplianceAgent},
+    consent::{ConsentRegistry, DEFAULT_CONSENT_PURPOSE},
     coordinator::AgentCoordinator,
-    models::pii_classifier::PiiClassifier,
-    utils::{demo_data::DemoData, chatbot_demo::ChatbotDemoData},
+    policy::CompliancePolicy,
+    pseudonym::Pseudonymizer,
+    hashing::KeyedHasher,
+    synthetic::Synthesizer,
+    llm::LlmSetup,
+    generalization,
+    types::{GuardrailDecision, PiiType},
+    vault::{TokenVault, VAULT_ACCESS_KEY_ENV},
+    session_store::{self, InMemorySessionStore, SessionStore, SqliteSessionStore},
+    web_server::ServerConfig,
+    models::{evaluation::{self, ModeReport}, pii_classifier::{PiiClassifier, DEFAULT_MODEL_PATH}},
+    utils::{demo_data::DemoData, chatbot_demo::ChatbotDemoData, corpus::{self, CorpusFormat, LoadedCorpus}},
 };
 
 #[derive(Parser)]
@@ -12,6 +25,30 @@ use pii_compliance_agent::{
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    
+    /// Redaction policy file (defaults to config/policy.toml if present)
+    #[arg(long, global = true)]
+    policy: Option<String>,
+    
+    /// Token vault file (defaults to data/token_vault.json); the key is read from PII_VAULT_KEY
+    #[arg(long, global = true)]
+    vault: Option<String>,
+    
+    /// Pseudonym numbering for session and tenant scopes (defaults to data/pseudonyms.json); keyed by PII_PSEUDONYM_KEY
+    #[arg(long, global = true)]
+    pseudonyms: Option<String>,
+    
+    /// OpenAI-compatible LLM settings (defaults to config/llm.toml if present)
+    #[arg(long, global = true)]
+    llm_config: Option<String>,
+    
+    /// Consent registry file (defaults to data/consent.json)
+    #[arg(long, global = true)]
+    consent: Option<String>,
+    
+    /// Audit log file; requires PII_AUDIT_KEY, and defaults to data/audit.jsonl when only the key is set
+    #[arg(long, global = true)]
+    audit: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -25,19 +62,104 @@ enum Commands {
         /// Output file for results (optional)
         #[arg(short, long)]
         output: Option<String>,
+        
+        /// Trained model file (defaults to models/pii_classifier.json if present)
+        #[arg(short, long)]
+        model: Option<String>,
     },
-    /// Train the PII classifier with demo data
+    /// Train the PII classifier with demo data or an annotated corpus
     Train {
         /// Number of training epochs
         #[arg(short, long, default_value = "100")]
         epochs: usize,
+        
+        /// Annotated corpus (JSONL, CoNLL BIO or spaCy JSON); defaults to the demo data
+        #[arg(short, long)]
+        data: Option<String>,
+        
+        /// Corpus format: jsonl, conll or spacy (inferred from the extension if omitted)
+        #[arg(short, long)]
+        format: Option<CorpusFormat>,
+        
+        /// Abort instead of skipping misaligned annotations
+        #[arg(long)]
+        strict: bool,
+        
+        /// Where to write the trained model
+        #[arg(short, long, default_value = DEFAULT_MODEL_PATH)]
+        output: String,
+    },
+    /// Measure precision, recall and F1 per PII type on an annotated corpus
+    Eval {
+        /// Annotated corpus (JSONL, CoNLL BIO or spaCy JSON)
+        #[arg(short, long)]
+        data: String,
+        
+        /// Corpus format: jsonl, conll or spacy (inferred from the extension if omitted)
+        #[arg(short, long)]
+        format: Option<CorpusFormat>,
+        
+        /// Trained model file (defaults to models/pii_classifier.json if present)
+        #[arg(short, long)]
+        model: Option<String>,
+        
+        /// Write the full report as JSON (optional)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Report quasi-identifier groups smaller than k across a batch of records
+    KAnonymity {
+        /// Text file with one record per line
+        #[arg(short, long)]
+        input: String,
+        
+        /// Minimum group size
+        #[arg(short, long, default_value = "5")]
+        k: usize,
+        
+        /// Quasi-identifier types, generalized per the policy's [generalization] settings
+        #[arg(short, long, value_delimiter = ',', value_parser = parse_pii_type,
+              default_value = "DateOfBirth,Address,IpAddress")]
+        quasi: Vec<PiiType>,
+        
+        /// Trained model file (defaults to models/pii_classifier.json if present)
+        #[arg(short, long)]
+        model: Option<String>,
+        
+        /// Write the full report as JSON (optional)
+        #[arg(short, long)]
+        output: Option<String>,
     },
     /// Run a demo with sample data
     Demo,
     /// Run chatbot compliance demo
-    ChatbotDemo,
+    ChatbotDemo {
+        /// SQLite database for chat sessions (e.g. data/sessions.db); sessions are kept in memory without one
+        #[arg(long)]
+        session_db: Option<String>,
+        
+        /// Hours a session is kept after its last message; defaults to the
+        /// policy's `[retention] redacted_content_days`
+        #[arg(long)]
+        session_ttl_hours: Option<i64>,
+    },
+    /// Look up the original values behind vault tokens (requires PII_VAULT_ACCESS_KEY)
+    Detokenize {
+        /// Tokens to resolve, e.g. tok_email_8f3a...
+        #[arg(required = true)]
+        tokens: Vec<String>,
+    },
+    /// Check that the audit log's hash chain is intact
+    VerifyAudit {
+        /// Audit log to check (defaults to --audit, then data/audit.jsonl)
+        file: Option<String>,
+    },
     /// Start web server for API endpoints
-    Serve,
+    Serve {
+        /// Trained model file (defaults to models/pii_classifier.json if present)
+        #[arg(short, long)]
+        model: Option<String>,
+    },
 }
 
 #[tokio::main]
@@ -45,16 +167,33 @@ async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
     
     let cli = Cli::parse();
+    let policy = Arc::new(CompliancePolicy::load_or_default(cli.policy.as_deref())?);
+    let vault = TokenVault::for_policy(&policy, cli.vault.as_deref())?.map(Arc::new);
+    let pseudonymizer = Pseudonymizer::for_policy(&policy, cli.pseudonyms.as_deref())?.map(Arc::new);
+    let hasher = KeyedHasher::for_policy(&policy)?.map(Arc::new);
+    let synthesizer = Synthesizer::for_policy(&policy)?.map(Arc::new);
+    let llm = LlmSetup::load(cli.llm_config.as_deref())?;
+    let extractor = LlmExtractorAgent::from_setup(&llm);
+    let verifier = LlmVerifierAgent::from_setup(&llm);
+    let open_audit = || AuditLog::open_configured(cli.audit.as_deref()).map(|audit| audit.map(Arc::new));
     
     match cli.command {
-        Commands::Scan { text, output } => {
+        Commands::Scan { text, output, model } => {
             println!("🔍 Scanning text for PII...");
             
             // Initialize coordinator and agents
-            let coordinator = AgentCoordinator::new();
-            let scanner = PiiScannerAgent::new();
-            let enforcer = ComplianceEnforcerAgent::new();
-            let reasoner = LlmReasonerAgent::new();
+            let coordinator = AgentCoordinator::new()
+                .with_extractor(extractor.clone())
+                .with_verifier(verifier.clone())
+                .with_audit(open_audit()?);
+            let scanner = PiiScannerAgent::with_classifier(PiiClassifier::load_or_default(model.as_deref())?);
+            let enforcer = ComplianceEnforcerAgent::new()
+                .with_policy(policy.clone())
+                .with_vault(vault.clone())
+                .with_pseudonymizer(pseudonymizer.clone())
+                .with_hasher(hasher.clone())
+                .with_synthesizer(synthesizer.clone());
+            let reasoner = LlmReasonerAgent::new().with_backend(llm.backend.clone(), llm.prompts.clone());
             
             // Run the compliance pipeline
             let result = coordinator.run_compliance_pipeline(&text, scanner, enforcer, reasoner).await?;
@@ -69,16 +208,68 @@ async fn main() -> anyhow::Result<()> {
             }
         }
         
-        Commands::Train { epochs } => {
+        Commands::Train { epochs, data, format, strict, output } => {
             println!("🧠 Training PII classifier...");
             
+            let training = match &data {
+                Some(path) => corpus::load(path, format)?,
+                None => corpus::validate(DemoData::new().get_training_data(), "demo data"),
+            };
+            report_corpus_issues(&training);
+            if strict && !training.issues.is_empty() {
+                anyhow::bail!("{} misaligned annotations; fix them or drop --strict", training.issues.len());
+            }
+            
             let mut classifier = PiiClassifier::new();
-            let demo_data = DemoData::new();
+            classifier.train(&training.examples, epochs).await?;
+            classifier.save(&output)?;
+            
+            println!("✅ Training completed! Model saved to {}", output);
+        }
+        
+        Commands::Eval { data, format, model, output } => {
+            println!("📏 Evaluating PII classifier on {}...", data);
+            
+            let classifier = PiiClassifier::load_or_default(model.as_deref())?;
+            let samples = corpus::load(&data, format)?;
+            report_corpus_issues(&samples);
+            let report = evaluation::evaluate(&classifier, &samples.examples);
+            
+            print_mode_report("Exact span match", &report.exact);
+            print_mode_report("Overlap match", &report.overlap);
+            
+            if let Some(output_path) = output {
+                std::fs::write(&output_path, serde_json::to_string_pretty(&report)?)?;
+                println!("✅ Report saved to {}", &output_path);
+            }
+        }
+        
+        Commands::KAnonymity { input, k, quasi, model, output } => {
+            println!("🧮 Checking {}-anonymity of {}...", k, input);
+            
+            let classifier = PiiClassifier::load_or_default(model.as_deref())?;
+            let contents = std::fs::read_to_string(&input)?;
+            let records: Vec<_> = contents.lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| policy.filter(classifier.detect_pii(line)))
+                .collect();
             
-            classifier.train(&demo_data.get_training_data(), epochs).await?;
-            classifier.save("models/pii_classifier.json")?;
+            let report = generalization::k_anonymity(&records, &quasi, &policy.generalization, k);
+            println!("📊 {} records in {} groups", report.records, report.groups);
+            if report.satisfied {
+                println!("✅ Every group has at least {} records", k);
+            } else {
+                println!("⚠️  {} groups have fewer than {} records:", report.violations.len(), k);
+                for group in &report.violations {
+                    let values: Vec<String> = group.values.iter().map(|(t, v)| format!("{}={}", t, v)).collect();
+                    println!("  - {} record(s): {}", group.count, values.join(", "));
+                }
+            }
             
-            println!("✅ Training completed! Model saved to models/pii_classifier.json");
+            if let Some(output_path) = output {
+                std::fs::write(&output_path, serde_json::to_string_pretty(&report)?)?;
+                println!("✅ Report saved to {}", &output_path);
+            }
         }
         
         Commands::Demo => {
@@ -87,10 +278,18 @@ async fn main() -> anyhow::Result<()> {
             let demo_data = DemoData::new();
             let sample_texts = demo_data.get_demo_texts();
             
-            let coordinator = AgentCoordinator::new();
-            let scanner = PiiScannerAgent::new();
-            let enforcer = ComplianceEnforcerAgent::new();
-            let reasoner = LlmReasonerAgent::new();
+            let coordinator = AgentCoordinator::new()
+                .with_extractor(extractor.clone())
+                .with_verifier(verifier.clone())
+                .with_audit(open_audit()?);
+            let scanner = PiiScannerAgent::with_classifier(PiiClassifier::load_or_default(None)?);
+            let enforcer = ComplianceEnforcerAgent::new()
+                .with_policy(policy.clone())
+                .with_vault(vault.clone())
+                .with_pseudonymizer(pseudonymizer.clone())
+                .with_hasher(hasher.clone())
+                .with_synthesizer(synthesizer.clone());
+            let reasoner = LlmReasonerAgent::new().with_backend(llm.backend.clone(), llm.prompts.clone());
             
             for (i, text) in sample_texts.iter().enumerate() {
                 println!("\n--- Demo {} ---", i + 1);
@@ -102,18 +301,55 @@ async fn main() -> anyhow::Result<()> {
             }
         }
         
-        Commands::ChatbotDemo => {
+        Commands::ChatbotDemo { session_db, session_ttl_hours } => {
             println!("🤖 Running Chatbot Compliance Demo...");
             
+            let session_ttl = session_ttl_hours
+                .map(chrono::Duration::hours)
+                .unwrap_or_else(|| chrono::Duration::days(policy.retention.redacted_content_days));
+            session_store::warn_if_shorter_than_retention(session_ttl, &policy.retention);
+            let sessions: Arc<dyn SessionStore> = match &session_db {
+                Some(path) => Arc::new(SqliteSessionStore::open(path, session_ttl)?),
+                None => Arc::new(InMemorySessionStore::new(session_ttl)),
+            };
+            
+            let consent = Arc::new(ConsentRegistry::open_or_default(cli.consent.as_deref())?);
+            
             let chatbot_demo = ChatbotDemoData::new();
             let scenarios = chatbot_demo.get_chat_scenarios();
-            let compliance_agent = ChatbotComplianceAgent::new();
+            let compliance_agent = ChatbotComplianceAgent::new()
+                .with_classifier(PiiClassifier::load_or_default(None)?)
+                .with_extractor(extractor.clone())
+                .with_policy(policy.clone())
+                .with_vault(vault.clone())
+                .with_pseudonymizer(pseudonymizer.clone())
+                .with_hasher(hasher.clone())
+                .with_synthesizer(synthesizer.clone())
+                .with_backend(llm.backend.clone(), llm.prompts.clone())
+                .with_session_store(sessions)
+                .with_consent(Some(consent), DEFAULT_CONSENT_PURPOSE)
+                .with_audit(open_audit()?);
+            
+            let evicted = compliance_agent.evict_expired_sessions()?;
+            if evicted > 0 {
+                println!("🧹 Evicted {} expired session(s)", evicted);
+            }
+            let retention = compliance_agent.sweep_retention().await?;
+            if retention.raw_purged + retention.messages_removed + retention.values_scrubbed > 0 {
+                println!("🧹 Retention: {} raw message(s) purged, {} removed, {} stored value(s) scrubbed, {} violation(s)",
+                         retention.raw_purged, retention.messages_removed, retention.values_scrubbed, retention.violations);
+            }
+            let sweeper = compliance_agent.spawn_retention_sweeper();
             
             for (i, scenario) in scenarios.iter().enumerate() {
                 println!("\n=== Chatbot Scenario {} ===", i + 1);
                 println!("Session ID: {}", scenario.session_id);
                 println!("User ID: {}", scenario.user_id);
                 println!("Risk Level: {:?}", scenario.risk_level);
+                if let Some(purpose) = &scenario.purpose {
+                    println!("Purpose: {}", purpose);
+                    compliance_agent.set_session_purpose(&scenario.session_id, &scenario.user_id, purpose).await?;
+                }
                 
                 // Process each message in the conversation
                 for (msg_idx, message) in scenario.messages.iter().enumerate() {
@@ -135,24 +371,119 @@ async fn main() -> anyhow::Result<()> {
                                    pii.pii_type, pii.value, pii.confidence);
                         }
                         
+                        for split in &compliance_result.cross_message_pii {
+                            println!("  🧩 {:?} split across {} messages: {}",
+                                   split.detection.pii_type, split.message_ids.len(), split.detection.value);
+                        }
+                        
                         for rec in &compliance_result.recommendations {
                             println!("  💡 {}", rec);
                         }
+                    } else {
+                        // Check the bot reply before it reaches the user
+                        let guardrail = compliance_agent.process_bot_message(message.clone()).await?;
+                        
+                        println!("🛡️  Guardrail: {:?}", guardrail.decision);
+                        if guardrail.decision != GuardrailDecision::Passed {
+                            println!("  Disclosed: {} items", guardrail.compliance_result.detected_pii.len());
+                            println!("  Delivered: {}", guardrail.delivered_text);
+                        }
                     }
                 }
                 
                 println!("\n📊 Session Summary:");
                 println!("  Total Messages: {}", scenario.messages.len());
                 println!("  User Messages: {}", scenario.messages.iter().filter(|m| m.is_user_message).count());
-                println!("  Risk Level: {:?}", scenario.risk_level);
+                println!("  Expected Risk Level: {:?}", scenario.risk_level);
+                println!("  Session Risk Level: {:?}", compliance_agent.session_risk_level(&scenario.session_id)?);
+                if let Some(session) = compliance_agent.session(&scenario.session_id)? {
+                    let violations: Vec<_> = session.compliance_violations.iter().map(|v| &v.violation_type).collect();
+                    println!("  Violations: {:?}", violations);
+                }
             }
+            
+            sweeper.abort();
         }
         
-        Commands::Serve => {
+        Commands::Detokenize { tokens } => {
+            let vault = vault.ok_or_else(|| anyhow::anyhow!("no token vault configured; set PII_VAULT_KEY"))?;
+            let access_key = std::env::var(VAULT_ACCESS_KEY_ENV).unwrap_or_default();
+            
+            for token in &tokens {
+                match vault.detokenize(token, &access_key) {
+                    Ok(value) => println!("{}\t{}", token, value),
+                    Err(e) => println!("{}\t❌ {}", token, e),
+                }
+            }
+        }
+        
+        Commands::VerifyAudit { file } => {
+            let path = file.or(cli.audit.clone()).unwrap_or_else(|| DEFAULT_AUDIT_PATH.to_string());
+            println!("🔏 Verifying audit log {}...", path);
+            
+            match AuditLog::verify(&path, audit::key_from_env()?.as_bytes()) {
+                Ok(verification) => {
+                    println!("✅ Chain intact: {} entries", verification.entries);
+                    println!("   Head: {}", verification.head);
+                }
+                Err(e) => {
+                    println!("❌ Audit log has been tampered with or is unreadable");
+                    return Err(e);
+                }
+            }
+        }
+        
+        Commands::Serve { model } => {
             println!("🌐 Starting web server...");
-            pii_compliance_agent::web_server::start_web_server().await?;
+            let config = ServerConfig {
+                model_path: model,
+                policy_path: cli.policy.clone(),
+                vault_path: cli.vault.clone(),
+                pseudonym_path: cli.pseudonyms.clone(),
+                llm_config_path: cli.llm_config.clone(),
+                consent_path: cli.consent.clone(),
+                audit_path: cli.audit.clone(),
+            };
+            pii_compliance_agent::web_server::start_web_server(config).await?;
         }
     }
     
     Ok(())
-} 
\ No newline at end of file
+}
+
+fn print_mode_report(title: &str, report: &ModeReport) {
+    println!("\n📊 {}", title);
+    println!("  {:<30} {:>5} {:>5} {:>5} {:>9} {:>9} {:>9}", "Type", "TP", "FP", "FN", "Precision", "Recall", "F1");
+    
+    let rows = report.per_type.iter()
+        .map(|(pii_type, m)| (pii_type.as_str(), m))
+        .chain(std::iter::once(("Overall", &report.overall)));
+    for (pii_type, m) in rows {
+        println!(
+            "  {:<30} {:>5} {:>5} {:>5} {:>9.3} {:>9.3} {:>9.3}",
+            pii_type, m.true_positives, m.false_positives, m.false_negatives, m.precision, m.recall, m.f1
+        );
+    }
+    
+    println!("  Confusion (gold -> predicted):");
+    for (gold, row) in &report.confusion {
+        let cells: Vec<String> = row.iter().map(|(predicted, n)| format!("{}={}", predicted, n)).collect();
+        println!("    {:<28} {}", gold, cells.join(", "));
+    }
+}
+
+fn parse_pii_type(label: &str) -> Result<PiiType, String> {
+    corpus::parse_label(label).ok_or_else(|| format!("unknown PII type {:?}", label))
+}
+
+fn report_corpus_issues(corpus: &LoadedCorpus) {
+    let annotations: usize = corpus.examples.iter().map(|e| e.pii_annotations.len()).sum();
+    println!("📚 Loaded {} examples with {} annotations", corpus.examples.len(), annotations);
+    
+    if !corpus.issues.is_empty() {
+        println!("⚠️  Skipped {} annotations:", corpus.issues.len());
+        for issue in &corpus.issues {
+            println!("  - {}", issue);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use crate::models::pii_classifier::{ModelMetadata, PiiClassifier};
+use crate::types::{PiiDetection, TrainingData};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Row/column label in the confusion matrix for "no matching span"
+pub const NO_SPAN: &str = "None";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchMode {
+    /// Predicted and gold spans must have identical offsets
+    Exact,
+    /// Predicted and gold spans only need to share at least one byte
+    Overlap,
+}
+
+impl MatchMode {
+    fn matches(self, predicted: &PiiDetection, gold: &PiiDetection) -> bool {
+        match self {
+            MatchMode::Exact => predicted.start_pos == gold.start_pos && predicted.end_pos == gold.end_pos,
+            MatchMode::Overlap => predicted.start_pos < gold.end_pos && gold.start_pos < predicted.end_pos,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TypeMetrics {
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub false_negatives: usize,
+    pub precision: f32,
+    pub recall: f32,
+    pub f1: f32,
+}
+
+impl TypeMetrics {
+    fn finalize(&mut self) {
+        let tp = self.true_positives as f32;
+        self.precision = ratio(tp, tp + self.false_positives as f32);
+        self.recall = ratio(tp, tp + self.false_negatives as f32);
+        self.f1 = ratio(2.0 * self.precision * self.recall, self.precision + self.recall);
+    }
+}
+
+fn ratio(numerator: f32, denominator: f32) -> f32 {
+    if denominator == 0.0 { 0.0 } else { numerator / denominator }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModeReport {
+    /// Keyed by `PiiType` debug name
+    pub per_type: BTreeMap<String, TypeMetrics>,
+    /// Micro-averaged over all types
+    pub overall: TypeMetrics,
+    /// `confusion[gold][predicted]`; `None` marks a missed or spurious span
+    pub confusion: BTreeMap<String, BTreeMap<String, usize>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvaluationReport {
+    pub samples: usize,
+    pub model: ModelMetadata,
+    pub exact: ModeReport,
+    pub overlap: ModeReport,
+}
+
+/// Runs `classifier.detect_pii` over every sample and scores the result
+/// against its annotations under both exact-span and overlap matching
+pub fn evaluate(classifier: &PiiClassifier, data: &[TrainingData]) -> EvaluationReport {
+    let predictions: Vec<Vec<PiiDetection>> = data.iter()
+        .map(|sample| classifier.detect_pii(&sample.text))
+        .collect();
+
+    EvaluationReport {
+        samples: data.len(),
+        model: classifier.metadata().clone(),
+        exact: score(data, &predictions, MatchMode::Exact),
+        overlap: score(data, &predictions, MatchMode::Overlap),
+    }
+}
+
+fn score(data: &[TrainingData], predictions: &[Vec<PiiDetection>], mode: MatchMode) -> ModeReport {
+    let mut report = ModeReport::default();
+
+    for (sample, predicted) in data.iter().zip(predictions) {
+        let gold = &sample.pii_annotations;
+
+        // Per-type counts: each prediction may satisfy at most one gold span
+        let mut used = vec![false; predicted.len()];
+        for g in gold {
+            let hit = predicted.iter().enumerate()
+                .position(|(i, p)| !used[i] && p.pii_type == g.pii_type && mode.matches(p, g));
+            let metrics = report.per_type.entry(type_name(g)).or_default();
+            match hit {
+                Some(i) => {
+                    used[i] = true;
+                    metrics.true_positives += 1;
+                }
+                None => metrics.false_negatives += 1,
+            }
+        }
+        for (p, _) in predicted.iter().zip(&used).filter(|(_, used)| !**used) {
+            report.per_type.entry(type_name(p)).or_default().false_positives += 1;
+        }
+
+        // Confusion matrix pairs spans regardless of type
+        let mut used = vec![false; predicted.len()];
+        for g in gold {
+            let hit = predicted.iter().enumerate()
+                .position(|(i, p)| !used[i] && mode.matches(p, g));
+            let column = match hit {
+                Some(i) => {
+                    used[i] = true;
+                    type_name(&predicted[i])
+                }
+                None => NO_SPAN.to_string(),
+            };
+            *report.confusion.entry(type_name(g)).or_default().entry(column).or_default() += 1;
+        }
+        for (p, _) in predicted.iter().zip(&used).filter(|(_, used)| !**used) {
+            *report.confusion.entry(NO_SPAN.to_string()).or_default().entry(type_name(p)).or_default() += 1;
+        }
+    }
+
+    for metrics in report.per_type.values_mut() {
+        metrics.finalize();
+        report.overall.true_positives += metrics.true_positives;
+        report.overall.false_positives += metrics.false_positives;
+        report.overall.false_negatives += metrics.false_negatives;
+    }
+    report.overall.finalize();
+
+    report
+}
+
+fn type_name(detection: &PiiDetection) -> String {
+    format!("{:?}", detection.pii_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::PiiType;
+
+    fn span(pii_type: PiiType, start_pos: usize, end_pos: usize) -> PiiDetection {
+        PiiDetection { pii_type, start_pos, end_pos, ..Default::default() }
+    }
+
+    fn sample(gold: Vec<PiiDetection>) -> TrainingData {
+        TrainingData { text: String::new(), pii_annotations: gold }
+    }
+
+    /// Gold email, phone and SSN; predicted an exact email, a phone one byte
+    /// short, a phone over the SSN's span and a spurious email
+    fn scored(mode: MatchMode) -> ModeReport {
+        let data = vec![
+            sample(vec![span(PiiType::Email, 10, 20), span(PiiType::PhoneNumber, 30, 42)]),
+            sample(vec![span(PiiType::SocialSecurityNumber, 0, 11)]),
+        ];
+        let predictions = vec![
+            vec![span(PiiType::Email, 10, 20), span(PiiType::PhoneNumber, 31, 42)],
+            vec![span(PiiType::PhoneNumber, 0, 11), span(PiiType::Email, 50, 60)],
+        ];
+        score(&data, &predictions, mode)
+    }
+
+    fn assert_metrics(metrics: &TypeMetrics, counts: (usize, usize, usize), scores: (f32, f32, f32)) {
+        assert_eq!((metrics.true_positives, metrics.false_positives, metrics.false_negatives), counts);
+        for (actual, expected) in [(metrics.precision, scores.0), (metrics.recall, scores.1), (metrics.f1, scores.2)] {
+            assert!((actual - expected).abs() < 1e-4, "{:?} vs {:?}", metrics, scores);
+        }
+    }
+
+    fn confusion(report: &ModeReport, gold: &str, predicted: &str) -> usize {
+        report.confusion.get(gold).and_then(|row| row.get(predicted)).copied().unwrap_or(0)
+    }
+
+    #[test]
+    fn exact_matching_counts_an_off_by_one_span_as_a_miss() {
+        let report = scored(MatchMode::Exact);
+
+        assert_metrics(&report.per_type["Email"], (1, 1, 0), (0.5, 1.0, 2.0 / 3.0));
+        assert_metrics(&report.per_type["PhoneNumber"], (0, 2, 1), (0.0, 0.0, 0.0));
+        assert_metrics(&report.per_type["SocialSecurityNumber"], (0, 0, 1), (0.0, 0.0, 0.0));
+        // P = 1/4, R = 1/3, F1 = 2PR / (P + R) = 2/7
+        assert_metrics(&report.overall, (1, 3, 2), (0.25, 1.0 / 3.0, 2.0 / 7.0));
+
+        assert_eq!(confusion(&report, "Email", "Email"), 1);
+        assert_eq!(confusion(&report, "PhoneNumber", NO_SPAN), 1);
+        assert_eq!(confusion(&report, "SocialSecurityNumber", "PhoneNumber"), 1);
+        assert_eq!(confusion(&report, NO_SPAN, "PhoneNumber"), 1);
+        assert_eq!(confusion(&report, NO_SPAN, "Email"), 1);
+    }
+
+    #[test]
+    fn overlap_matching_accepts_an_off_by_one_span() {
+        let report = scored(MatchMode::Overlap);
+
+        assert_metrics(&report.per_type["Email"], (1, 1, 0), (0.5, 1.0, 2.0 / 3.0));
+        assert_metrics(&report.per_type["PhoneNumber"], (1, 1, 0), (0.5, 1.0, 2.0 / 3.0));
+        assert_metrics(&report.per_type["SocialSecurityNumber"], (0, 0, 1), (0.0, 0.0, 0.0));
+        // P = 2/4, R = 2/3, F1 = 4/7
+        assert_metrics(&report.overall, (2, 2, 1), (0.5, 2.0 / 3.0, 4.0 / 7.0));
+
+        assert_eq!(confusion(&report, "PhoneNumber", "PhoneNumber"), 1);
+        assert_eq!(confusion(&report, "SocialSecurityNumber", "PhoneNumber"), 1);
+        assert_eq!(confusion(&report, NO_SPAN, "Email"), 1);
+        assert_eq!(confusion(&report, NO_SPAN, "PhoneNumber"), 0);
+    }
+
+    #[test]
+    fn touching_spans_do_not_overlap() {
+        assert!(!MatchMode::Overlap.matches(&span(PiiType::Email, 0, 10), &span(PiiType::Email, 10, 20)));
+        assert!(MatchMode::Overlap.matches(&span(PiiType::Email, 0, 11), &span(PiiType::Email, 10, 20)));
+    }
+}
@@ -1,3 +1,6 @@
+pub mod evaluation;
 pub mod pii_classifier;
+pub mod token_tagger;
+pub mod validators;
 
-pub use pii_classifier::PiiClassifier; 
\ No newline at end of file
+pub use pii_classifier::{resolve_spans, PiiClassifier};
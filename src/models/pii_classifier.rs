@@ -1,12 +1,124 @@
+use crate::models::token_tagger::{TokenTagger, TAGGER_TYPES};
+use crate::models::validators;
 use crate::types::{PiiDetection, PiiType, TrainingData};
-use anyhow::Result;
-use regex::Regex;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use regex::{Match, Regex};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::path::Path;
+
+/// Default location written by `train` and picked up by `scan` and `serve`
+pub const DEFAULT_MODEL_PATH: &str = "models/pii_classifier.json";
+
+/// Bumped whenever `ModelFile` changes in a way older readers cannot handle
+pub const MODEL_FORMAT_VERSION: u32 = 1;
+
+/// Thresholds are never calibrated below this, so unverified candidates
+/// (e.g. cards failing the Luhn check) stay suppressed
+const MIN_THRESHOLD: f32 = 0.5;
+
+/// How far back (in characters) to look for context words before a candidate
+const CONTEXT_WINDOW_CHARS: usize = 40;
+
+/// A single detection pattern with the base confidence of its matches.
+/// When `context` is set the pattern only fires if the context regex matches
+/// within `CONTEXT_WINDOW_CHARS` before the candidate.
+#[derive(Debug, Clone)]
+struct PatternRule {
+    regex: Regex,
+    confidence: f32,
+    context: Option<Regex>,
+}
+
+impl PatternRule {
+    fn compile(spec: &PatternSpec) -> Result<Self> {
+        let regex = Regex::new(&spec.pattern)
+            .with_context(|| format!("invalid {:?} pattern {:?}", spec.pii_type, spec.pattern))?;
+        let context = spec.context.as_deref()
+            .map(Regex::new)
+            .transpose()
+            .with_context(|| format!("invalid {:?} context pattern {:?}", spec.pii_type, spec.context))?;
+        
+        Ok(Self {
+            regex,
+            confidence: spec.confidence,
+            context,
+        })
+    }
+    
+    fn new(pattern: &str, confidence: f32) -> Self {
+        Self {
+            regex: Regex::new(pattern).unwrap(),
+            confidence,
+            context: None,
+        }
+    }
+    
+    fn with_context(pattern: &str, confidence: f32, context: &str) -> Self {
+        Self {
+            context: Some(Regex::new(context).unwrap()),
+            ..Self::new(pattern, confidence)
+        }
+    }
+    
+    fn context_matches(&self, text: &str, start: usize) -> bool {
+        let Some(context) = &self.context else {
+            return true;
+        };
+        
+        let window_start = text[..start]
+            .char_indices()
+            .rev()
+            .nth(CONTEXT_WINDOW_CHARS - 1)
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+        
+        context.is_match(&text[window_start..start])
+    }
+}
+
+/// Serialized form of a `PatternRule`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatternSpec {
+    pub pii_type: PiiType,
+    pub pattern: String,
+    pub confidence: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelMetadata {
+    pub trained_at: Option<DateTime<Utc>>,
+    /// SHA-256 of the serialized training corpus
+    pub corpus_hash: Option<String>,
+    pub training_samples: usize,
+    pub epochs: usize,
+}
+
+/// On-disk model format written by `PiiClassifier::save`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelFile {
+    pub format_version: u32,
+    pub metadata: ModelMetadata,
+    pub confidence_threshold: f32,
+    /// Learned per-type thresholds; types not listed use `confidence_threshold`
+    pub thresholds: HashMap<PiiType, f32>,
+    pub patterns: Vec<PatternSpec>,
+    /// Statistical tagger for types no regex covers; absent until trained
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_model: Option<TokenTagger>,
+}
 
 #[derive(Debug, Clone)]
 pub struct PiiClassifier {
-    patterns: HashMap<PiiType, Vec<Regex>>,
+    patterns: HashMap<PiiType, Vec<PatternRule>>,
     confidence_threshold: f32,
+    thresholds: HashMap<PiiType, f32>,
+    metadata: ModelMetadata,
+    token_model: Option<TokenTagger>,
 }
 
 impl PiiClassifier {
@@ -16,104 +128,543 @@ impl PiiClassifier {
         // Initialize regex patterns for different PII types
         patterns.insert(
             PiiType::Email,
-            vec![Regex::new(r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Z|a-z]{2,}\b").unwrap()],
+            vec![PatternRule::new(r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Z|a-z]{2,}\b", 0.9)],
         );
         
         patterns.insert(
             PiiType::PhoneNumber,
             vec![
-                Regex::new(r"\b\d{3}[-.]?\d{3}[-.]?\d{4}\b").unwrap(),
-                Regex::new(r"\b\(\d{3}\)\s*\d{3}[-.]?\d{4}\b").unwrap(),
+                PatternRule::new(r"\b\d{3}[-.]?\d{3}[-.]?\d{4}\b", 0.9),
+                PatternRule::new(r"\b\(\d{3}\)\s*\d{3}[-.]?\d{4}\b", 0.9),
             ],
         );
         
+        // 9xx area numbers are re-labelled as ITINs during validation, so the
+        // SSN patterns cover both. Bare nine-digit runs need a nearby keyword.
         patterns.insert(
             PiiType::SocialSecurityNumber,
-            vec![Regex::new(r"\b\d{3}-\d{2}-\d{4}\b").unwrap()],
+            vec![
+                PatternRule::new(r"\b\d{3}[- ]\d{2}[- ]\d{4}\b", 0.95),
+                PatternRule::with_context(
+                    r"\b\d{9}\b",
+                    0.85,
+                    r"(?i)\b(ssn|social security|social|itin|taxpayer)\b|\bss#",
+                ),
+            ],
+        );
+        
+        patterns.insert(
+            PiiType::EmployerIdentificationNumber,
+            vec![
+                PatternRule::new(r"\b\d{2}-\d{7}\b", 0.85),
+                PatternRule::with_context(
+                    r"\b\d{9}\b",
+                    0.85,
+                    r"(?i)\b(ein|fein|employer identification|tax id)\b",
+                ),
+            ],
         );
         
+        // Card candidates are 13-19 digits with optional separators; they are
+        // only reported once the issuer range and Luhn checksum are verified
         patterns.insert(
             PiiType::CreditCardNumber,
-            vec![Regex::new(r"\b\d{4}[- ]?\d{4}[- ]?\d{4}[- ]?\d{4}\b").unwrap()],
+            vec![PatternRule::new(r"\b\d(?:[- ]?\d){12,18}\b", 0.95)],
         );
         
         patterns.insert(
             PiiType::IpAddress,
-            vec![Regex::new(r"\b\d{1,3}\.\d{1,3}\.\d{1,3}\.\d{1,3}\b").unwrap()],
+            vec![PatternRule::new(r"\b\d{1,3}\.\d{1,3}\.\d{1,3}\.\d{1,3}\b", 0.9)],
         );
         
         Self {
             patterns,
             confidence_threshold: 0.7,
+            thresholds: HashMap::new(),
+            metadata: ModelMetadata::default(),
+            token_model: None,
         }
     }
     
-    pub async fn train(&mut self, training_data: &[TrainingData], _epochs: usize) -> Result<()> {
-        // Simplified training for hackathon demo
-        // In production, this would use actual ML training
+    pub fn metadata(&self) -> &ModelMetadata {
+        &self.metadata
+    }
+    
+    pub fn threshold_for(&self, pii_type: &PiiType) -> f32 {
+        self.thresholds.get(pii_type).copied().unwrap_or(self.confidence_threshold)
+    }
+    
+    pub async fn train(&mut self, training_data: &[TrainingData], epochs: usize) -> Result<()> {
         println!("Training PII classifier with {} samples", training_data.len());
         
-        // Update confidence threshold based on training data
-        let total_detections: usize = training_data.iter()
-            .map(|data| data.pii_annotations.len())
-            .sum();
+        let tagged = training_data.iter()
+            .flat_map(|data| &data.pii_annotations)
+            .filter(|a| TAGGER_TYPES.contains(&a.pii_type))
+            .count();
+        if tagged > 0 {
+            println!("Training token tagger on {} annotations for {} epochs", tagged, epochs);
+            self.token_model = Some(TokenTagger::train(training_data, epochs));
+        }
+        
+        // Calibrate a threshold per type: pick the candidate confidence that
+        // maximizes F1 against the annotations
+        let candidates: Vec<(Vec<PiiDetection>, &TrainingData)> = training_data.iter()
+            .map(|data| (self.detect_candidates(&data.text), data))
+            .collect();
+        
+        let mut types: Vec<PiiType> = self.patterns.keys().cloned().collect();
+        types.push(PiiType::IndividualTaxpayerNumber);
+        if self.token_model.is_some() {
+            types.extend(TAGGER_TYPES);
+        }
         
-        if total_detections > 0 {
-            self.confidence_threshold = 0.8; // Higher confidence for trained model
+        for pii_type in types {
+            let mut levels: Vec<f32> = candidates.iter()
+                .flat_map(|(detections, _)| detections.iter())
+                .filter(|d| d.pii_type == pii_type)
+                .map(|d| d.confidence.max(MIN_THRESHOLD))
+                .collect();
+            // Ties go to the lowest level, so patterns the corpus never
+            // exercises keep the default rather than being silenced
+            levels.push(self.confidence_threshold);
+            levels.sort_by(f32::total_cmp);
+            levels.dedup();
+            
+            let best = levels.into_iter()
+                .map(|level| (level, f1_at_threshold(&candidates, &pii_type, level)))
+                .filter(|(_, f1)| *f1 > 0.0)
+                .max_by(|a, b| a.1.total_cmp(&b.1).then(b.0.total_cmp(&a.0)));
+            
+            if let Some((level, f1)) = best {
+                println!("  {:?}: threshold {:.2} (F1 {:.2})", pii_type, level, f1);
+                self.thresholds.insert(pii_type, level);
+            }
         }
         
-        println!("Training completed! Confidence threshold set to {}", self.confidence_threshold);
+        let corpus = serde_json::to_vec(training_data)?;
+        self.metadata = ModelMetadata {
+            trained_at: Some(Utc::now()),
+            corpus_hash: Some(format!("{:x}", Sha256::digest(&corpus))),
+            training_samples: training_data.len(),
+            epochs,
+        };
+        
+        println!("Training completed! Calibrated {} per-type thresholds", self.thresholds.len());
         Ok(())
     }
     
     pub fn detect_pii(&self, text: &str) -> Vec<PiiDetection> {
-        let mut detections = vec![];
-        
-        // Use regex patterns for initial detection
-        for (pii_type, patterns) in &self.patterns {
-            for pattern in patterns {
-                for mat in pattern.find_iter(text) {
-                    detections.push(PiiDetection {
-                        pii_type: pii_type.clone(),
-                        confidence: 0.9, // High confidence for regex matches
-                        start_pos: mat.start(),
-                        end_pos: mat.end(),
-                        value: mat.as_str().to_string(),
-                    });
+        let mut detections = self.detect_candidates(text);
+        
+        // Drop candidates whose validated confidence falls below the threshold
+        detections.retain(|d| d.confidence >= self.threshold_for(&d.pii_type));
+        
+        resolve_spans(text, detections)
+    }
+    
+    /// Validated regex matches and tagger spans before thresholding and
+    /// span resolution
+    fn detect_candidates(&self, text: &str) -> Vec<PiiDetection> {
+        let mut detections = match &self.token_model {
+            Some(tagger) => tagger.detect(text),
+            None => vec![],
+        };
+        
+        for (pii_type, rules) in &self.patterns {
+            for rule in rules {
+                for mat in rule.regex.find_iter(text) {
+                    if !rule.context_matches(text, mat.start()) {
+                        continue;
+                    }
+                    
+                    if let Some(detection) = self.validate_match(pii_type, rule.confidence, mat) {
+                        detections.push(detection);
+                    }
                 }
             }
         }
         
-        // Additional pattern-based detection for edge cases
-        // In production, this would use ML-based detection
-        for detection in &mut detections {
-            if detection.confidence < self.confidence_threshold {
-                detection.confidence = self.confidence_threshold;
+        detections
+    }
+    
+    /// Applies type-specific validation to a regex match. Returns `None` when the
+    /// match is structurally impossible for its type.
+    fn validate_match(&self, pii_type: &PiiType, confidence: f32, mat: Match) -> Option<PiiDetection> {
+        let mut detection = PiiDetection {
+            pii_type: pii_type.clone(),
+            confidence,
+            start_pos: mat.start(),
+            end_pos: mat.end(),
+            value: mat.as_str().to_string(),
+            ..Default::default()
+        };
+        
+        match pii_type {
+            PiiType::CreditCardNumber => {
+                let mut digits = validators::digits_only(mat.as_str());
+                
+                // The greedy match can take in digit groups after the card,
+                // such as a CVV; fall back to the card they follow
+                if !validators::luhn_valid(&digits) {
+                    if let Some(card) = valid_card_prefix(mat.as_str()) {
+                        detection.end_pos = mat.start() + card.len();
+                        detection.value = card.to_string();
+                        digits = validators::digits_only(card);
+                    }
+                }
+                
+                // Order numbers and tracking IDs rarely fall in an issuer range
+                let network = validators::card_network(&digits)?;
+                
+                detection.card_network = Some(network);
+                if !validators::luhn_valid(&digits) {
+                    detection.confidence = 0.4;
+                }
             }
+            PiiType::SocialSecurityNumber => {
+                let digits = validators::digits_only(mat.as_str());
+                
+                if validators::itin_valid(&digits) {
+                    detection.pii_type = PiiType::IndividualTaxpayerNumber;
+                } else if !validators::ssn_valid(&digits) {
+                    return None;
+                }
+            }
+            PiiType::EmployerIdentificationNumber
+                if !validators::ein_valid(&validators::digits_only(mat.as_str())) =>
+            {
+                return None;
+            }
+            _ => {}
         }
         
-        detections
+        Some(detection)
     }
     
-    fn extract_features(&self, text: &str) -> Vec<f32> {
-        // Simplified feature extraction for hackathon demo
-        vec![
-            text.len() as f32,
-            text.chars().filter(|c| c.is_ascii_digit()).count() as f32,
-            text.chars().filter(|c| c.is_ascii_alphabetic()).count() as f32,
-            text.chars().filter(|c| *c == '@').count() as f32,
-            text.chars().filter(|c| *c == '.').count() as f32,
-            text.chars().filter(|c| *c == '-').count() as f32,
-        ]
+    pub fn to_model_file(&self) -> ModelFile {
+        let mut patterns: Vec<PatternSpec> = self.patterns.iter()
+            .flat_map(|(pii_type, rules)| rules.iter().map(move |rule| PatternSpec {
+                pii_type: pii_type.clone(),
+                pattern: rule.regex.as_str().to_string(),
+                confidence: rule.confidence,
+                context: rule.context.as_ref().map(|c| c.as_str().to_string()),
+            }))
+            .collect();
+        // HashMap order is random; keep saved files diffable
+        patterns.sort_by_key(|p| std::cmp::Reverse(type_priority(&p.pii_type)));
+        
+        ModelFile {
+            format_version: MODEL_FORMAT_VERSION,
+            metadata: self.metadata.clone(),
+            confidence_threshold: self.confidence_threshold,
+            thresholds: self.thresholds.clone(),
+            patterns,
+            token_model: self.token_model.clone(),
+        }
     }
     
-    pub fn save(&self, _path: &str) -> Result<()> {
-        // No-op for web server - no file system access needed
+    pub fn from_model_file(model: ModelFile) -> Result<Self> {
+        if model.format_version > MODEL_FORMAT_VERSION {
+            anyhow::bail!(
+                "model format version {} is newer than supported version {}",
+                model.format_version,
+                MODEL_FORMAT_VERSION
+            );
+        }
+        
+        let mut patterns: HashMap<PiiType, Vec<PatternRule>> = HashMap::new();
+        for spec in &model.patterns {
+            patterns.entry(spec.pii_type.clone())
+                .or_default()
+                .push(PatternRule::compile(spec)?);
+        }
+        
+        Ok(Self {
+            patterns,
+            confidence_threshold: model.confidence_threshold,
+            thresholds: model.thresholds,
+            metadata: model.metadata,
+            token_model: model.token_model,
+        })
+    }
+    
+    pub fn save(&self, path: &str) -> Result<()> {
+        if let Some(parent) = Path::new(path).parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        
+        let json = serde_json::to_string_pretty(&self.to_model_file())?;
+        std::fs::write(path, json).with_context(|| format!("failed to write model to {}", path))?;
         Ok(())
     }
     
-    pub fn load(_path: &str) -> Result<Self> {
-        // No-op for web server - always return a new instance
-        Ok(Self::new())
+    pub fn load(path: &str) -> Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read model from {}", path))?;
+        let value: serde_json::Value = serde_json::from_str(&json)
+            .with_context(|| format!("{} is not valid JSON", path))?;
+        
+        // Check the version before the schema so newer files fail clearly
+        let version = value["format_version"].as_u64().unwrap_or(0);
+        if version > MODEL_FORMAT_VERSION as u64 {
+            anyhow::bail!(
+                "{} uses model format version {}, newer than supported version {}",
+                path,
+                version,
+                MODEL_FORMAT_VERSION
+            );
+        }
+        
+        let model: ModelFile = serde_json::from_value(value)
+            .with_context(|| format!("{} is not a valid model file", path))?;
+        Self::from_model_file(model)
+    }
+    
+    /// Loads `path` if given, otherwise `DEFAULT_MODEL_PATH` when it exists,
+    /// falling back to the built-in patterns
+    pub fn load_or_default(path: Option<&str>) -> Result<Self> {
+        match path {
+            Some(path) => Self::load(path),
+            None if Path::new(DEFAULT_MODEL_PATH).exists() => Self::load(DEFAULT_MODEL_PATH),
+            None => Ok(Self::new()),
+        }
+    }
+}
+
+impl Default for PiiClassifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// F1 for one type over the training corpus when candidates below
+/// `threshold` are dropped. Matching is by span overlap.
+fn f1_at_threshold(candidates: &[(Vec<PiiDetection>, &TrainingData)], pii_type: &PiiType, threshold: f32) -> f32 {
+    let (mut tp, mut fp, mut fn_) = (0usize, 0usize, 0usize);
+    
+    for (detections, data) in candidates {
+        let kept: Vec<&PiiDetection> = detections.iter()
+            .filter(|d| d.pii_type == *pii_type && d.confidence >= threshold)
+            .collect();
+        let gold: Vec<&PiiDetection> = data.pii_annotations.iter()
+            .filter(|a| a.pii_type == *pii_type)
+            .collect();
+        let overlaps = |a: &PiiDetection, b: &PiiDetection| a.start_pos < b.end_pos && b.start_pos < a.end_pos;
+        
+        tp += kept.iter().filter(|d| gold.iter().any(|g| overlaps(d, g))).count();
+        fp += kept.iter().filter(|d| !gold.iter().any(|g| overlaps(d, g))).count();
+        fn_ += gold.iter().filter(|g| !kept.iter().any(|d| overlaps(d, g))).count();
     }
-} 
\ No newline at end of file
+    
+    if tp == 0 {
+        return 0.0;
+    }
+    2.0 * tp as f32 / (2 * tp + fp + fn_) as f32
+}
+
+/// Arbitration order when spans of different types overlap. Validated
+/// identifiers beat looser patterns such as phone numbers.
+fn type_priority(pii_type: &PiiType) -> u8 {
+    match pii_type {
+        PiiType::CreditCardNumber => 10,
+        PiiType::SocialSecurityNumber => 9,
+        PiiType::IndividualTaxpayerNumber => 8,
+        PiiType::EmployerIdentificationNumber => 7,
+        PiiType::Email => 6,
+        PiiType::IpAddress => 5,
+        PiiType::PhoneNumber => 4,
+        PiiType::DateOfBirth => 3,
+        PiiType::Address => 2,
+        PiiType::Name => 1,
+        PiiType::Unknown => 0,
+    }
+}
+
+/// Turns raw detections into a sorted set of non-overlapping spans.
+///
+/// Overlapping detections of the same type are merged into one span covering
+/// both. Overlaps between different types are arbitrated by type priority,
+/// then confidence, then span length; the loser is dropped. Detections whose
+/// offsets do not fall on `text` char boundaries are discarded.
+pub fn resolve_spans(text: &str, detections: Vec<PiiDetection>) -> Vec<PiiDetection> {
+    let mut candidates: Vec<PiiDetection> = detections
+        .into_iter()
+        .filter(|d| d.start_pos < d.end_pos && text.get(d.start_pos..d.end_pos).is_some())
+        .collect();
+    
+    // Merge same-type overlaps first so arbitration sees the full extent
+    candidates.sort_by_key(|d| (type_priority(&d.pii_type), d.start_pos));
+    let mut merged: Vec<PiiDetection> = Vec::with_capacity(candidates.len());
+    for detection in candidates {
+        match merged.last_mut() {
+            Some(last) if last.pii_type == detection.pii_type && detection.start_pos < last.end_pos => {
+                last.end_pos = last.end_pos.max(detection.end_pos);
+                if detection.confidence > last.confidence {
+                    last.confidence = detection.confidence;
+                    last.card_network = detection.card_network.or(last.card_network);
+                }
+                last.value = text[last.start_pos..last.end_pos].to_string();
+            }
+            _ => merged.push(detection),
+        }
+    }
+    
+    merged.sort_by(|a, b| {
+        type_priority(&b.pii_type)
+            .cmp(&type_priority(&a.pii_type))
+            .then(b.confidence.total_cmp(&a.confidence))
+            .then((b.end_pos - b.start_pos).cmp(&(a.end_pos - a.start_pos)))
+            .then(a.start_pos.cmp(&b.start_pos))
+    });
+    
+    let mut resolved: Vec<PiiDetection> = vec![];
+    for detection in merged {
+        let overlaps = resolved
+            .iter()
+            .any(|kept| detection.start_pos < kept.end_pos && kept.start_pos < detection.end_pos);
+        if !overlaps {
+            resolved.push(detection);
+        }
+    }
+    
+    resolved.sort_by_key(|d| d.start_pos);
+    resolved
+}
+
+/// Longest part of a card candidate, ending at a separator, whose 13-19
+/// digits fall in an issuer range and pass the Luhn check
+fn valid_card_prefix(candidate: &str) -> Option<&str> {
+    candidate.char_indices()
+        .rev()
+        .filter(|(_, c)| *c == ' ' || *c == '-')
+        .map(|(i, _)| &candidate[..i])
+        .find(|prefix| {
+            let digits = validators::digits_only(prefix);
+            (13..=19).contains(&digits.len())
+                && validators::card_network(&digits).is_some()
+                && validators::luhn_valid(&digits)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn types(detections: &[PiiDetection]) -> Vec<PiiType> {
+        detections.iter().map(|d| d.pii_type.clone()).collect()
+    }
+
+    #[test]
+    fn card_followed_by_more_digits_is_still_found() {
+        let classifier = PiiClassifier::new();
+        for (text, card) in [
+            ("Card 4111111111111111 123 on file", "4111111111111111"),
+            ("Card 4111 1111 1111 1111 123 on file", "4111 1111 1111 1111"),
+        ] {
+            let cards: Vec<String> = classifier.detect_pii(text).into_iter()
+                .filter(|d| d.pii_type == PiiType::CreditCardNumber)
+                .map(|d| d.value)
+                .collect();
+            assert_eq!(cards, vec![card], "{}", text);
+        }
+
+        // An unbroken run of digits is not cut down to a valid prefix
+        assert!(classifier.detect_pii("Ref 4111111111111111123").iter().all(|d| d.pii_type != PiiType::CreditCardNumber));
+    }
+
+    #[test]
+    fn bare_ssn_needs_a_context_word() {
+        let classifier = PiiClassifier::new();
+        assert_eq!(types(&classifier.detect_pii("SSN 123456789")), vec![PiiType::SocialSecurityNumber]);
+        assert_eq!(types(&classifier.detect_pii("ss# 123456789")), vec![PiiType::SocialSecurityNumber]);
+        assert!(classifier.detect_pii("order 123456789").is_empty());
+    }
+
+    fn detection(pii_type: PiiType, text: &str, start_pos: usize, end_pos: usize, confidence: f32) -> PiiDetection {
+        PiiDetection {
+            pii_type,
+            confidence,
+            start_pos,
+            end_pos,
+            value: text[start_pos..end_pos].to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn resolve_spans_merges_same_type_overlaps() {
+        let text = "call 555-123-4567 now";
+        let resolved = resolve_spans(text, vec![
+            detection(PiiType::PhoneNumber, text, 5, 12, 0.6),
+            detection(PiiType::PhoneNumber, text, 9, 17, 0.9),
+        ]);
+
+        assert_eq!(resolved.len(), 1);
+        assert_eq!((resolved[0].start_pos, resolved[0].end_pos), (5, 17));
+        assert_eq!(resolved[0].value, "555-123-4567");
+        assert_eq!(resolved[0].confidence, 0.9);
+    }
+
+    #[test]
+    fn resolve_spans_keeps_the_higher_priority_type() {
+        let text = "card 4111 1111 1111 1111";
+        let resolved = resolve_spans(text, vec![
+            detection(PiiType::PhoneNumber, text, 5, 19, 0.95),
+            detection(PiiType::CreditCardNumber, text, 5, 24, 0.9),
+        ]);
+
+        assert_eq!(types(&resolved), vec![PiiType::CreditCardNumber]);
+    }
+
+    #[test]
+    fn resolve_spans_prefers_type_priority_over_confidence() {
+        let text = "Jane Smith Street";
+        let resolved = resolve_spans(text, vec![
+            detection(PiiType::Name, text, 0, 10, 0.99),
+            detection(PiiType::Address, text, 5, 17, 0.6),
+        ]);
+
+        assert_eq!(types(&resolved), vec![PiiType::Address]);
+    }
+
+    #[test]
+    fn resolve_spans_sorts_and_drops_invalid_offsets() {
+        let text = "héllo a@b.co 10.0.0.1";
+        let resolved = resolve_spans(text, vec![
+            detection(PiiType::IpAddress, text, 14, 22, 0.9),
+            detection(PiiType::Email, text, 7, 13, 0.9),
+            PiiDetection { pii_type: PiiType::Name, start_pos: 1, end_pos: 2, ..Default::default() },
+            PiiDetection { pii_type: PiiType::Name, start_pos: 5, end_pos: 5, ..Default::default() },
+            PiiDetection { pii_type: PiiType::Name, start_pos: 20, end_pos: 40, ..Default::default() },
+        ]);
+
+        assert_eq!(types(&resolved), vec![PiiType::Email, PiiType::IpAddress]);
+    }
+
+    #[test]
+    fn saved_model_loads_with_the_same_behaviour() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("model.json");
+        let path = path.to_str().unwrap();
+
+        let mut classifier = PiiClassifier::new();
+        classifier.thresholds.insert(PiiType::PhoneNumber, 0.8);
+        classifier.save(path).unwrap();
+
+        let loaded = PiiClassifier::load(path).unwrap();
+        assert_eq!(loaded.threshold_for(&PiiType::PhoneNumber), 0.8);
+        let text = "SSN 123-45-6789, card 4111 1111 1111 1111, mail a@b.co";
+        assert_eq!(types(&loaded.detect_pii(text)), types(&classifier.detect_pii(text)));
+    }
+
+    #[test]
+    fn newer_model_format_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.json");
+        std::fs::write(&path, format!(r#"{{"format_version": {}}}"#, MODEL_FORMAT_VERSION + 1)).unwrap();
+
+        let error = PiiClassifier::load(path.to_str().unwrap()).unwrap_err();
+        assert!(error.to_string().contains("newer than supported"));
+    }
+}
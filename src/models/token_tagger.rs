@@ -0,0 +1,277 @@
+use crate::types::{PiiDetection, PiiType, TrainingData};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Types the tagger learns. These have no reliable regex, so they are only
+/// found by the statistical model.
+pub const TAGGER_TYPES: [PiiType; 3] = [PiiType::Name, PiiType::Address, PiiType::DateOfBirth];
+
+const LEARNING_RATE: f32 = 0.2;
+const L2_PENALTY: f32 = 1e-4;
+
+/// A word-like token and its byte range in the source text
+#[derive(Debug, Clone)]
+pub struct Token<'a> {
+    pub text: &'a str,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Splits text into words, keeping internal separators so dates such as
+/// 05/15/1985 and hyphenated names stay single tokens
+pub fn tokenize(text: &str) -> Vec<Token<'_>> {
+    static TOKEN: OnceLock<Regex> = OnceLock::new();
+    let token = TOKEN.get_or_init(|| Regex::new(r"\w+(?:[/\-.']\w+)*").unwrap());
+
+    token
+        .find_iter(text)
+        .map(|m| Token { text: m.as_str(), start: m.start(), end: m.end() })
+        .collect()
+}
+
+/// Collapsed character-class shape: "Smith" -> "Xx", "05/15/1985" -> "d/d/d"
+fn word_shape(word: &str) -> String {
+    let mut shape = String::new();
+    for c in word.chars() {
+        let class = if c.is_uppercase() {
+            'X'
+        } else if c.is_lowercase() {
+            'x'
+        } else if c.is_ascii_digit() {
+            'd'
+        } else {
+            c
+        };
+        if !shape.ends_with(class) {
+            shape.push(class);
+        }
+    }
+    shape
+}
+
+/// Sparse character, shape and context features for the token at `i`
+pub fn extract_features(tokens: &[Token], i: usize) -> Vec<String> {
+    let word = tokens[i].text;
+    let lower = word.to_lowercase();
+    let chars: Vec<char> = lower.chars().collect();
+
+    let mut features = vec![
+        "bias".to_string(),
+        format!("w={}", lower),
+        format!("shape={}", word_shape(word)),
+        format!("pre3={}", chars.iter().take(3).collect::<String>()),
+        format!("suf3={}", chars[chars.len().saturating_sub(3)..].iter().collect::<String>()),
+        format!("len={}", chars.len().min(8)),
+    ];
+
+    if word.chars().next().is_some_and(|c| c.is_uppercase()) {
+        features.push("cap".to_string());
+    }
+    if word.chars().all(|c| c.is_ascii_digit()) {
+        features.push("digits".to_string());
+    }
+
+    for (offset, label) in [(-2, "-2"), (-1, "-1"), (1, "+1"), (2, "+2")] {
+        let j = i as isize + offset;
+        match tokens.get(j as usize).filter(|_| j >= 0) {
+            Some(other) => {
+                features.push(format!("w{}={}", label, other.text.to_lowercase()));
+                features.push(format!("shape{}={}", label, word_shape(other.text)));
+            }
+            None => features.push(format!("w{}=<none>", label)),
+        }
+    }
+
+    features
+}
+
+/// Multinomial logistic-regression token tagger. Class 0 is "not PII";
+/// class `k > 0` is `labels[k - 1]`. Weights are stored sparsely per feature.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenTagger {
+    pub labels: Vec<PiiType>,
+    pub weights: HashMap<String, Vec<f32>>,
+}
+
+impl TokenTagger {
+    /// Trains with plain SGD over `epochs` passes. A token is labelled with
+    /// the annotation type it overlaps, if that type is one of `TAGGER_TYPES`.
+    pub fn train(training_data: &[TrainingData], epochs: usize) -> Self {
+        let mut tagger = Self {
+            labels: TAGGER_TYPES.to_vec(),
+            weights: HashMap::new(),
+        };
+
+        let examples: Vec<(Vec<String>, usize)> = training_data
+            .iter()
+            .flat_map(|data| {
+                let tokens = tokenize(&data.text);
+                (0..tokens.len())
+                    .map(|i| {
+                        let class = data
+                            .pii_annotations
+                            .iter()
+                            .find(|a| a.start_pos < tokens[i].end && tokens[i].start < a.end_pos)
+                            .and_then(|a| tagger.class_of(&a.pii_type))
+                            .unwrap_or(0);
+                        (extract_features(&tokens, i), class)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        let classes = tagger.labels.len() + 1;
+        for epoch in 0..epochs {
+            let rate = LEARNING_RATE / (1.0 + epoch as f32 * 0.05);
+            for (features, gold) in &examples {
+                let probs = tagger.probabilities(features);
+                for feature in features {
+                    let weights = tagger.weights.entry(feature.clone()).or_insert_with(|| vec![0.0; classes]);
+                    for (class, weight) in weights.iter_mut().enumerate() {
+                        let target = if class == *gold { 1.0 } else { 0.0 };
+                        *weight += rate * ((target - probs[class]) - L2_PENALTY * *weight);
+                    }
+                }
+            }
+        }
+
+        tagger
+    }
+
+    fn class_of(&self, pii_type: &PiiType) -> Option<usize> {
+        self.labels.iter().position(|l| l == pii_type).map(|i| i + 1)
+    }
+
+    fn probabilities(&self, features: &[String]) -> Vec<f32> {
+        let mut scores = vec![0.0f32; self.labels.len() + 1];
+        for weights in features.iter().filter_map(|f| self.weights.get(f)) {
+            for (score, weight) in scores.iter_mut().zip(weights) {
+                *score += weight;
+            }
+        }
+
+        let max = scores.iter().copied().fold(f32::MIN, f32::max);
+        let exp: Vec<f32> = scores.iter().map(|s| (s - max).exp()).collect();
+        let total: f32 = exp.iter().sum();
+        exp.into_iter().map(|e| e / total).collect()
+    }
+
+    /// Tags every token and joins runs of the same label into detections.
+    /// A run may bridge commas and spaces (e.g. "12 Main St, Springfield").
+    /// Confidence is the mean probability of the label across the run.
+    pub fn detect(&self, text: &str) -> Vec<PiiDetection> {
+        let tokens = tokenize(text);
+        let mut detections: Vec<PiiDetection> = vec![];
+        let mut last_class = 0;
+        let mut run_probs: Vec<f32> = vec![];
+
+        for i in 0..tokens.len() {
+            let probs = self.probabilities(&extract_features(&tokens, i));
+            let (class, prob) = probs
+                .iter()
+                .copied()
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap_or((0, 1.0));
+
+            let bridged = text[detections.last().map_or(0, |d| d.end_pos)..tokens[i].start]
+                .chars()
+                .all(|c| c == ',' || c.is_whitespace());
+
+            match detections.last_mut() {
+                Some(current) if class != 0 && class == last_class && bridged => {
+                    current.end_pos = tokens[i].end;
+                    current.value = text[current.start_pos..current.end_pos].to_string();
+                    run_probs.push(prob);
+                    current.confidence = run_probs.iter().sum::<f32>() / run_probs.len() as f32;
+                }
+                _ if class != 0 => {
+                    run_probs = vec![prob];
+                    detections.push(PiiDetection {
+                        pii_type: self.labels[class - 1].clone(),
+                        confidence: prob,
+                        start_pos: tokens[i].start,
+                        end_pos: tokens[i].end,
+                        value: tokens[i].text.to_string(),
+                        ..Default::default()
+                    });
+                }
+                _ => {}
+            }
+
+            last_class = class;
+        }
+
+        detections
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::PiiClassifier;
+
+    /// `template` with `{}` filled by `value`, annotated as `pii_type`
+    fn example(template: &str, value: &str, pii_type: PiiType) -> TrainingData {
+        let start_pos = template.find("{}").unwrap();
+        TrainingData {
+            text: template.replace("{}", value),
+            pii_annotations: vec![PiiDetection {
+                pii_type,
+                confidence: 1.0,
+                start_pos,
+                end_pos: start_pos + value.len(),
+                value: value.to_string(),
+                ..Default::default()
+            }],
+        }
+    }
+
+    fn corpus() -> Vec<TrainingData> {
+        let mut corpus = vec![];
+        for name in ["Alice Smith", "Bob Jones", "Maria Garcia", "Wei Chen"] {
+            corpus.push(example("My name is {} and I need help.", name, PiiType::Name));
+        }
+        for address in ["12 Main Street", "400 Oak Avenue", "9 Elm Road"] {
+            corpus.push(example("Please ship it to {} today.", address, PiiType::Address));
+        }
+        for dob in ["05/15/1985", "11/02/1990", "01/30/1978"] {
+            corpus.push(example("I was born on {} in Ohio.", dob, PiiType::DateOfBirth));
+        }
+        corpus.push(TrainingData { text: "The weather is nice and the order shipped.".to_string(), pii_annotations: vec![] });
+        corpus
+    }
+
+    fn found(tagger: &TokenTagger, text: &str) -> Vec<(PiiType, String)> {
+        tagger.detect(text).into_iter().map(|d| (d.pii_type, d.value)).collect()
+    }
+
+    #[test]
+    fn trained_tagger_finds_unseen_values_in_familiar_contexts() {
+        let tagger = TokenTagger::train(&corpus(), 30);
+
+        assert_eq!(found(&tagger, "My name is Carol White and I need help."), vec![(PiiType::Name, "Carol White".to_string())]);
+        assert_eq!(found(&tagger, "I was born on 07/04/1982 in Ohio."), vec![(PiiType::DateOfBirth, "07/04/1982".to_string())]);
+        assert!(found(&tagger, "The weather is nice.").is_empty());
+    }
+
+    #[test]
+    fn tagger_survives_the_model_file_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.json");
+        let path = path.to_str().unwrap();
+
+        let tagger = TokenTagger::train(&corpus(), 30);
+        let mut model = PiiClassifier::new().to_model_file();
+        model.token_model = Some(tagger.clone());
+        std::fs::write(path, serde_json::to_string(&model).unwrap()).unwrap();
+
+        let loaded = PiiClassifier::load(path).unwrap().to_model_file().token_model.unwrap();
+        assert_eq!(loaded.labels, tagger.labels);
+        assert_eq!(loaded.weights, tagger.weights);
+        let text = "My name is Carol White, born on 07/04/1982.";
+        assert_eq!(found(&loaded, text), found(&tagger, text));
+    }
+}
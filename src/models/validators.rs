@@ -0,0 +1,193 @@
+use crate::types::CardNetwork;
+
+/// Returns the digits of `value`, dropping separators such as spaces and dashes.
+pub fn digits_only(value: &str) -> String {
+    value.chars().filter(|c| c.is_ascii_digit()).collect()
+}
+
+/// Luhn (mod 10) checksum over a string of ASCII digits.
+pub fn luhn_valid(digits: &str) -> bool {
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return false;
+    }
+
+    let sum: u32 = digits
+        .chars()
+        .rev()
+        .enumerate()
+        .map(|(i, c)| {
+            let d = c.to_digit(10).unwrap_or(0);
+            if i % 2 == 1 {
+                let doubled = d * 2;
+                if doubled > 9 { doubled - 9 } else { doubled }
+            } else {
+                d
+            }
+        })
+        .sum();
+
+    sum.is_multiple_of(10)
+}
+
+/// Identifies the card network from the IIN/BIN prefix and the number length.
+/// Returns `None` when the digits do not fall in a known issuer range.
+pub fn card_network(digits: &str) -> Option<CardNetwork> {
+    let len = digits.len();
+    let prefix = |n: usize| -> u32 {
+        digits.get(..n).and_then(|p| p.parse().ok()).unwrap_or(0)
+    };
+
+    // Amex: 34, 37 - always 15 digits
+    if len == 15 && matches!(prefix(2), 34 | 37) {
+        return Some(CardNetwork::AmericanExpress);
+    }
+
+    // Visa: 4 - 13, 16 or 19 digits
+    if digits.starts_with('4') && matches!(len, 13 | 16 | 19) {
+        return Some(CardNetwork::Visa);
+    }
+
+    // Mastercard: 51-55 and 2221-2720 - 16 digits
+    if len == 16 && ((51..=55).contains(&prefix(2)) || (2221..=2720).contains(&prefix(4))) {
+        return Some(CardNetwork::Mastercard);
+    }
+
+    if (16..=19).contains(&len) {
+        // Discover: 6011, 644-649, 65 and the 622126-622925 co-branded range.
+        // Checked before UnionPay because the co-branded range also starts with 62.
+        if prefix(4) == 6011
+            || (644..=649).contains(&prefix(3))
+            || prefix(2) == 65
+            || (622126..=622925).contains(&prefix(6))
+        {
+            return Some(CardNetwork::Discover);
+        }
+
+        // JCB: 3528-3589
+        if (3528..=3589).contains(&prefix(4)) {
+            return Some(CardNetwork::Jcb);
+        }
+
+        // UnionPay: 62
+        if prefix(2) == 62 {
+            return Some(CardNetwork::UnionPay);
+        }
+    }
+
+    None
+}
+
+/// SSA structural rules: area is not 000, 666 or 9xx, group is not 00 and
+/// serial is not 0000.
+pub fn ssn_valid(digits: &str) -> bool {
+    if digits.len() != 9 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return false;
+    }
+
+    let area = &digits[..3];
+    let group = &digits[3..5];
+    let serial = &digits[5..];
+
+    area != "000" && area != "666" && !area.starts_with('9') && group != "00" && serial != "0000"
+}
+
+/// ITINs start with 9 and use group digits 50-65, 70-88, 90-92 or 94-99.
+pub fn itin_valid(digits: &str) -> bool {
+    if digits.len() != 9 || !digits.starts_with('9') {
+        return false;
+    }
+
+    match digits[3..5].parse::<u32>() {
+        Ok(group) => matches!(group, 50..=65 | 70..=88 | 90..=92 | 94..=99),
+        Err(_) => false,
+    }
+}
+
+/// EINs are nine digits whose two-digit prefix is an IRS-assigned campus code.
+pub fn ein_valid(digits: &str) -> bool {
+    if digits.len() != 9 {
+        return false;
+    }
+
+    match digits[..2].parse::<u32>() {
+        Ok(prefix) => matches!(
+            prefix,
+            1..=6 | 10..=16 | 20..=27 | 30..=39 | 40..=48 | 50..=68 | 71..=77 | 80..=88 | 90..=95 | 98..=99
+        ),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn luhn_accepts_valid_numbers() {
+        assert!(luhn_valid("4111111111111111"));
+        assert!(luhn_valid("378282246310005"));
+        assert!(luhn_valid("0"));
+    }
+
+    #[test]
+    fn luhn_rejects_bad_checksums_and_non_digits() {
+        assert!(!luhn_valid("4111111111111112"));
+        assert!(!luhn_valid(""));
+        assert!(!luhn_valid("4111-1111-1111-1111"));
+    }
+
+    #[test]
+    fn card_network_uses_prefix_and_length() {
+        assert_eq!(card_network("4111111111111111"), Some(CardNetwork::Visa));
+        assert_eq!(card_network("4222222222222"), Some(CardNetwork::Visa));
+        assert_eq!(card_network("378282246310005"), Some(CardNetwork::AmericanExpress));
+        assert_eq!(card_network("5555555555554444"), Some(CardNetwork::Mastercard));
+        assert_eq!(card_network("2221000000000009"), Some(CardNetwork::Mastercard));
+        assert_eq!(card_network("6011111111111117"), Some(CardNetwork::Discover));
+        assert_eq!(card_network("3530111333300000"), Some(CardNetwork::Jcb));
+        assert_eq!(card_network("6200000000000005"), Some(CardNetwork::UnionPay));
+    }
+
+    #[test]
+    fn card_network_prefers_discover_for_cobranded_unionpay() {
+        assert_eq!(card_network("6221260000000000"), Some(CardNetwork::Discover));
+        assert_eq!(card_network("6229260000000000"), Some(CardNetwork::UnionPay));
+    }
+
+    #[test]
+    fn card_network_rejects_wrong_lengths_and_unknown_ranges() {
+        assert_eq!(card_network("37828224631000"), None);
+        assert_eq!(card_network("41111111111111"), None);
+        assert_eq!(card_network("5555555555554"), None);
+        assert_eq!(card_network("1234567890123456"), None);
+    }
+
+    #[test]
+    fn ssn_rejects_never_issued_areas_groups_and_serials() {
+        assert!(ssn_valid("123456789"));
+        assert!(!ssn_valid("000456789"));
+        assert!(!ssn_valid("666456789"));
+        assert!(!ssn_valid("912456789"));
+        assert!(!ssn_valid("123006789"));
+        assert!(!ssn_valid("123450000"));
+        assert!(!ssn_valid("12345678"));
+    }
+
+    #[test]
+    fn itin_needs_area_9_and_an_assigned_group() {
+        assert!(itin_valid("912701234"));
+        assert!(itin_valid("900941234"));
+        assert!(!itin_valid("912691234"));
+        assert!(!itin_valid("912931234"));
+        assert!(!itin_valid("812701234"));
+    }
+
+    #[test]
+    fn ein_needs_a_campus_prefix() {
+        assert!(ein_valid("123456789"));
+        assert!(ein_valid("981234567"));
+        assert!(!ein_valid("001234567"));
+        assert!(!ein_valid("071234567"));
+        assert!(!ein_valid("961234567"));
+    }
+}
@@ -0,0 +1,651 @@
+use crate::types::{PiiDetection, PiiType, Severity};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Picked up automatically by the CLI and web server when present
+pub const DEFAULT_POLICY_PATH: &str = "config/policy.toml";
+
+/// What to do with a detected value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyAction {
+    /// Replace the value with the rule's replacement tag
+    Redact,
+    /// Mask letters and digits per the rule's `MaskSpec`, keeping separators and length
+    Mask,
+    /// Replace the value with a truncated HMAC-SHA256 join key, see `[hashing]`
+    Hash,
+    /// Swap the value for a reversible token stored in the token vault
+    Tokenize,
+    /// Replace each distinct value with a numbered tag, e.g. "[EMAIL_2]",
+    /// consistent within the configured pseudonymization scope
+    Pseudonymize,
+    /// Replace the value with a realistic fake of the same type and layout,
+    /// reproducible under the `[synthetic]` seed
+    Synthesize,
+    /// Coarsen the value per `[generalization]`: dates of birth to a year or
+    /// age band, IPs to a prefix, addresses to a city or ZIP3. Other types,
+    /// and values that cannot be parsed, are redacted
+    Generalize,
+    /// Leave the value in place
+    Allow,
+}
+
+/// Format-preserving partial mask. Only letters and digits count towards
+/// `keep_first`/`keep_last` and only they are masked; separators stay put.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaskSpec {
+    #[serde(default)]
+    pub keep_first: usize,
+    #[serde(default)]
+    pub keep_last: usize,
+    /// For emails, keep everything after the `@`; `keep_first`/`keep_last`
+    /// then apply to the local part
+    #[serde(default)]
+    pub keep_domain: bool,
+    #[serde(default = "default_mask_char")]
+    pub mask_char: char,
+}
+
+fn default_mask_char() -> char {
+    '*'
+}
+
+impl MaskSpec {
+    pub fn full() -> Self {
+        Self::keep(0, 0)
+    }
+
+    pub fn keep(keep_first: usize, keep_last: usize) -> Self {
+        Self {
+            keep_first,
+            keep_last,
+            keep_domain: false,
+            mask_char: default_mask_char(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyRule {
+    pub action: PolicyAction,
+    /// Replacement tag for `redact`, e.g. "[EMAIL]"
+    #[serde(default)]
+    pub replacement: Option<String>,
+    pub severity: Severity,
+    /// Detections below this confidence are ignored; defaults to the policy-wide value
+    #[serde(default)]
+    pub threshold: Option<f32>,
+    /// How `mask` reveals the value; defaults to the built-in spec for the type
+    #[serde(default)]
+    pub mask: Option<MaskSpec>,
+}
+
+/// How far a pseudonym mapping reaches
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PseudonymScope {
+    /// Numbering restarts for every scanned text
+    #[default]
+    Document,
+    /// Shared by all messages in a chat session; scans outside a session
+    /// fall back to document scope
+    Session,
+    /// Shared by everything processed for `tenant`
+    Tenant,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PseudonymConfig {
+    #[serde(default)]
+    pub scope: PseudonymScope,
+    /// Required for tenant scope
+    #[serde(default)]
+    pub tenant: Option<String>,
+}
+
+impl PseudonymConfig {
+    /// Key of the pseudonym table to use for a text
+    pub fn scope_key(&self, document_id: &str, session_id: Option<&str>) -> String {
+        match (self.scope, session_id, &self.tenant) {
+            (PseudonymScope::Tenant, _, Some(tenant)) => format!("tenant:{}", tenant),
+            (PseudonymScope::Session, Some(session_id), _) => format!("session:{}", session_id),
+            _ => format!("document:{}", document_id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateLevel {
+    /// "05/15/1985" becomes "1985"
+    #[default]
+    Year,
+    /// "05/15/1985" becomes e.g. "30-39"
+    AgeBand,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressLevel {
+    /// Everything after the street line, without the ZIP code
+    #[default]
+    City,
+    /// First three ZIP digits, falling back to the city
+    Zip3,
+}
+
+/// Settings for the `generalize` action and the k-anonymity check
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeneralizationConfig {
+    #[serde(default)]
+    pub date: DateLevel,
+    #[serde(default = "default_age_band_width")]
+    pub age_band_width: u32,
+    #[serde(default = "default_ipv4_prefix")]
+    pub ipv4_prefix: u8,
+    #[serde(default = "default_ipv6_prefix")]
+    pub ipv6_prefix: u8,
+    #[serde(default)]
+    pub address: AddressLevel,
+}
+
+fn default_age_band_width() -> u32 {
+    10
+}
+
+fn default_ipv4_prefix() -> u8 {
+    24
+}
+
+fn default_ipv6_prefix() -> u8 {
+    48
+}
+
+impl Default for GeneralizationConfig {
+    fn default() -> Self {
+        Self {
+            date: DateLevel::default(),
+            age_band_width: default_age_band_width(),
+            ipv4_prefix: default_ipv4_prefix(),
+            ipv6_prefix: default_ipv6_prefix(),
+            address: AddressLevel::default(),
+        }
+    }
+}
+
+/// Settings for the `hash` action; the secret comes from `PII_HASH_SECRET`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HashConfig {
+    /// Hex characters kept from the digest
+    #[serde(default = "default_hash_length")]
+    pub length: usize,
+    /// Lowercase emails, names and addresses and strip separators from
+    /// numbers before hashing, so trivially different spellings join
+    #[serde(default = "default_true")]
+    pub normalize: bool,
+}
+
+fn default_hash_length() -> usize {
+    16
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for HashConfig {
+    fn default() -> Self {
+        Self {
+            length: default_hash_length(),
+            normalize: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyntheticConfig {
+    /// Same seed, same fakes; change it to draw a different dataset
+    #[serde(default)]
+    pub seed: u64,
+}
+
+/// What the outbound guardrail does with a bot reply that discloses PII
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GuardrailAction {
+    /// Replace the whole reply with `block_message`
+    Block,
+    /// Apply the outbound policy to the reply
+    #[default]
+    Redact,
+    /// Ask the LLM to rewrite the redacted reply, falling back to redaction
+    /// when there is no LLM or every attempt still discloses PII
+    Regenerate,
+}
+
+/// Outbound checks on chatbot replies. Bot replies are held to a stricter
+/// version of the policy, see `CompliancePolicy::outbound`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuardrailConfig {
+    #[serde(default)]
+    pub action: GuardrailAction,
+    /// No rule threshold is applied above this for bot replies
+    #[serde(default = "default_guardrail_threshold")]
+    pub threshold: f32,
+    #[serde(default = "default_block_message")]
+    pub block_message: String,
+    #[serde(default = "default_regenerate_attempts")]
+    pub regenerate_attempts: u32,
+}
+
+fn default_guardrail_threshold() -> f32 {
+    0.5
+}
+
+fn default_block_message() -> String {
+    "Sorry, I can't share that information.".to_string()
+}
+
+fn default_regenerate_attempts() -> u32 {
+    2
+}
+
+impl Default for GuardrailConfig {
+    fn default() -> Self {
+        Self {
+            action: GuardrailAction::default(),
+            threshold: default_guardrail_threshold(),
+            block_message: default_block_message(),
+            regenerate_attempts: default_regenerate_attempts(),
+        }
+    }
+}
+
+/// How far back the chatbot agent looks for PII split across user messages,
+/// e.g. "my SSN is 123" followed by "45-6789"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatWindowConfig {
+    /// User messages rescanned together, including the newest; below 2 disables
+    #[serde(default = "default_window_messages")]
+    pub messages: usize,
+    /// Older messages drop out of the window once it exceeds this many bytes
+    #[serde(default = "default_window_max_chars")]
+    pub max_chars: usize,
+}
+
+fn default_window_messages() -> usize {
+    3
+}
+
+fn default_window_max_chars() -> usize {
+    512
+}
+
+impl Default for ChatWindowConfig {
+    fn default() -> Self {
+        Self {
+            messages: default_window_messages(),
+            max_chars: default_window_max_chars(),
+        }
+    }
+}
+
+/// How long the chatbot agent keeps stored chat messages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionConfig {
+    /// Raw message content is replaced by its redacted text after this long
+    #[serde(default = "default_raw_content_hours")]
+    pub raw_content_hours: i64,
+    /// Messages are removed from the session after this long
+    #[serde(default = "default_redacted_content_days")]
+    pub redacted_content_days: i64,
+    /// How often the sweeper enforces the windows. Data found more than one
+    /// interval past its window is reported as a `DataRetention` violation.
+    #[serde(default = "default_sweep_interval_secs")]
+    pub sweep_interval_secs: u64,
+    #[serde(default = "default_retention_severity")]
+    pub severity: Severity,
+}
+
+fn default_raw_content_hours() -> i64 {
+    24
+}
+
+fn default_redacted_content_days() -> i64 {
+    90
+}
+
+fn default_sweep_interval_secs() -> u64 {
+    300
+}
+
+fn default_retention_severity() -> Severity {
+    Severity::High
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            raw_content_hours: default_raw_content_hours(),
+            redacted_content_days: default_redacted_content_days(),
+            sweep_interval_secs: default_sweep_interval_secs(),
+            severity: default_retention_severity(),
+        }
+    }
+}
+
+/// What a conversation is for, and the PII it needs. PII outside `allowed`
+/// is flagged as a `DataMinimization` violation with `severity`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PurposeConfig {
+    #[serde(default)]
+    pub allowed: Vec<PiiType>,
+    #[serde(default = "default_minimization_severity")]
+    pub severity: Severity,
+}
+
+fn default_minimization_severity() -> Severity {
+    Severity::Medium
+}
+
+fn purpose(allowed: &[PiiType], severity: Severity) -> PurposeConfig {
+    PurposeConfig {
+        allowed: allowed.to_vec(),
+        severity,
+    }
+}
+
+/// Redaction policy shared by the compliance enforcer and chatbot agents
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompliancePolicy {
+    /// Free-form version recorded alongside every decision
+    pub version: String,
+    #[serde(default = "default_threshold")]
+    pub default_threshold: f32,
+    /// Types missing from the file fall back to the built-in rule
+    #[serde(default)]
+    pub rules: HashMap<PiiType, PolicyRule>,
+    #[serde(default)]
+    pub pseudonymization: PseudonymConfig,
+    #[serde(default)]
+    pub synthetic: SyntheticConfig,
+    #[serde(default)]
+    pub hashing: HashConfig,
+    #[serde(default)]
+    pub generalization: GeneralizationConfig,
+    #[serde(default)]
+    pub guardrail: GuardrailConfig,
+    #[serde(default)]
+    pub chat_window: ChatWindowConfig,
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    /// Session purposes by name; names missing from the file fall back to the built-ins
+    #[serde(default)]
+    pub purposes: HashMap<String, PurposeConfig>,
+}
+
+fn default_threshold() -> f32 {
+    0.7
+}
+
+fn rule(action: PolicyAction, replacement: &str, severity: Severity, mask: MaskSpec) -> PolicyRule {
+    PolicyRule {
+        action,
+        replacement: Some(replacement.to_string()),
+        severity,
+        threshold: None,
+        mask: Some(mask),
+    }
+}
+
+impl Default for CompliancePolicy {
+    fn default() -> Self {
+        use PolicyAction::Redact;
+
+        // Masks keep just enough to confirm identity with a customer: the
+        // last four of a card, the email's first letter and domain, the area code
+        let email_mask = MaskSpec { keep_domain: true, ..MaskSpec::keep(1, 0) };
+        
+        let mut rules = HashMap::new();
+        rules.insert(PiiType::Email, rule(Redact, "[EMAIL]", Severity::Medium, email_mask));
+        rules.insert(PiiType::PhoneNumber, rule(Redact, "[PHONE]", Severity::Medium, MaskSpec::keep(3, 0)));
+        rules.insert(PiiType::SocialSecurityNumber, rule(Redact, "[SSN]", Severity::Critical, MaskSpec::keep(0, 4)));
+        rules.insert(PiiType::IndividualTaxpayerNumber, rule(Redact, "[ITIN]", Severity::Critical, MaskSpec::keep(0, 4)));
+        rules.insert(PiiType::EmployerIdentificationNumber, rule(Redact, "[EIN]", Severity::High, MaskSpec::keep(0, 4)));
+        rules.insert(PiiType::CreditCardNumber, rule(Redact, "[CC]", Severity::High, MaskSpec::keep(0, 4)));
+        rules.insert(PiiType::IpAddress, rule(Redact, "[IP]", Severity::Low, MaskSpec::full()));
+        rules.insert(PiiType::DateOfBirth, rule(Redact, "[DOB]", Severity::High, MaskSpec::full()));
+        rules.insert(PiiType::Address, rule(Redact, "[ADDRESS]", Severity::Low, MaskSpec::full()));
+        rules.insert(PiiType::Name, rule(Redact, "[NAME]", Severity::Medium, MaskSpec::keep(1, 0)));
+        rules.insert(PiiType::Unknown, rule(Redact, "[PII]", Severity::Medium, MaskSpec::full()));
+
+        // Each purpose allows what that kind of conversation plausibly needs
+        let mut purposes = HashMap::new();
+        purposes.insert("support".to_string(), purpose(&[PiiType::Email, PiiType::PhoneNumber, PiiType::Name], Severity::Medium));
+        purposes.insert("appointment".to_string(), purpose(&[PiiType::Name, PiiType::DateOfBirth, PiiType::PhoneNumber], Severity::High));
+        purposes.insert("billing".to_string(), purpose(&[PiiType::Name, PiiType::Email, PiiType::CreditCardNumber, PiiType::Address], Severity::High));
+        purposes.insert("identity_verification".to_string(), purpose(&[PiiType::Name, PiiType::DateOfBirth, PiiType::SocialSecurityNumber], Severity::High));
+        purposes.insert("weather".to_string(), purpose(&[PiiType::Address], Severity::High));
+
+        Self {
+            version: "builtin-1".to_string(),
+            default_threshold: default_threshold(),
+            rules,
+            pseudonymization: PseudonymConfig::default(),
+            synthetic: SyntheticConfig::default(),
+            hashing: HashConfig::default(),
+            generalization: GeneralizationConfig::default(),
+            guardrail: GuardrailConfig::default(),
+            chat_window: ChatWindowConfig::default(),
+            retention: RetentionConfig::default(),
+            purposes,
+        }
+    }
+}
+
+impl CompliancePolicy {
+    /// Parses and validates a TOML policy file
+    pub fn load(path: &str) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read policy {}", path))?;
+        let policy: Self = toml::from_str(&contents)
+            .with_context(|| format!("{} is not a valid policy file", path))?;
+
+        policy.validated().with_context(|| format!("invalid policy {}", path))
+    }
+
+    /// Loads `path` if given, otherwise `DEFAULT_POLICY_PATH` when it exists,
+    /// falling back to the built-in rules
+    pub fn load_or_default(path: Option<&str>) -> Result<Self> {
+        match path {
+            Some(path) => Self::load(path),
+            None if Path::new(DEFAULT_POLICY_PATH).exists() => Self::load(DEFAULT_POLICY_PATH),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Fills in built-in rules for unlisted types and checks every rule
+    pub fn validated(mut self) -> Result<Self> {
+        if self.version.trim().is_empty() {
+            anyhow::bail!("policy version must not be empty");
+        }
+        if !(0.0..=1.0).contains(&self.default_threshold) {
+            anyhow::bail!("default_threshold {} must be between 0 and 1", self.default_threshold);
+        }
+
+        if !(8..=64).contains(&self.hashing.length) {
+            anyhow::bail!("hashing length {} must be between 8 and 64", self.hashing.length);
+        }
+        let generalization = &self.generalization;
+        if generalization.ipv4_prefix > 32 || generalization.ipv6_prefix > 128 || generalization.age_band_width == 0 {
+            anyhow::bail!("generalization needs ipv4_prefix <= 32, ipv6_prefix <= 128 and a non-zero age_band_width");
+        }
+        if !(0.0..=1.0).contains(&self.guardrail.threshold) {
+            anyhow::bail!("guardrail threshold {} must be between 0 and 1", self.guardrail.threshold);
+        }
+        let retention = &self.retention;
+        if retention.raw_content_hours <= 0 || retention.redacted_content_days <= 0 || retention.sweep_interval_secs == 0 {
+            anyhow::bail!("retention windows and sweep_interval_secs must be positive");
+        }
+        if retention.raw_content_hours > retention.redacted_content_days * 24 {
+            anyhow::bail!("retention keeps raw content longer than redacted content");
+        }
+        if self.pseudonymization.scope == PseudonymScope::Tenant
+            && self.pseudonymization.tenant.as_deref().is_none_or(str::is_empty)
+        {
+            anyhow::bail!("pseudonymization scope is tenant but no tenant is set");
+        }
+
+        let builtin = Self::default();
+        for (name, fallback) in builtin.purposes {
+            self.purposes.entry(name).or_insert(fallback);
+        }
+        for (pii_type, fallback) in builtin.rules {
+            let rule = self.rules.entry(pii_type).or_insert_with(|| fallback.clone());
+            if rule.mask.is_none() {
+                rule.mask = fallback.mask;
+            }
+            // Every action falls back to the replacement tag when it cannot apply
+            if rule.replacement.as_deref().is_none_or(str::is_empty) {
+                rule.replacement = fallback.replacement;
+            }
+        }
+
+        for (pii_type, rule) in &self.rules {
+            if let Some(threshold) = rule.threshold {
+                if !(0.0..=1.0).contains(&threshold) {
+                    anyhow::bail!("{:?}: threshold {} must be between 0 and 1", pii_type, threshold);
+                }
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// The policy applied to chatbot replies: thresholds are capped at the
+    /// guardrail threshold, and `allow` and `mask` become `redact`, since a
+    /// user may share what the bot must never repeat, even in part
+    pub fn outbound(&self) -> Self {
+        let mut policy = self.clone();
+        policy.default_threshold = policy.default_threshold.min(self.guardrail.threshold);
+        for rule in policy.rules.values_mut() {
+            rule.threshold = rule.threshold.map(|t| t.min(self.guardrail.threshold));
+            if matches!(rule.action, PolicyAction::Allow | PolicyAction::Mask) {
+                rule.action = PolicyAction::Redact;
+            }
+        }
+        policy
+    }
+
+    pub fn purpose(&self, name: &str) -> Option<&PurposeConfig> {
+        self.purposes.get(name)
+    }
+
+    /// Whether any rule applies `action`
+    pub fn uses_action(&self, action: PolicyAction) -> bool {
+        self.rules.values().any(|r| r.action == action)
+    }
+
+    pub fn rule(&self, pii_type: &PiiType) -> Option<&PolicyRule> {
+        self.rules.get(pii_type)
+    }
+
+    pub fn action(&self, pii_type: &PiiType) -> PolicyAction {
+        self.rule(pii_type).map(|r| r.action).unwrap_or(PolicyAction::Redact)
+    }
+
+    pub fn severity(&self, pii_type: &PiiType) -> Severity {
+        self.rule(pii_type).map(|r| r.severity.clone()).unwrap_or(Severity::Medium)
+    }
+
+    pub fn threshold(&self, pii_type: &PiiType) -> f32 {
+        self.rule(pii_type).and_then(|r| r.threshold).unwrap_or(self.default_threshold)
+    }
+
+    pub fn mask(&self, pii_type: &PiiType) -> MaskSpec {
+        self.rule(pii_type).and_then(|r| r.mask.clone()).unwrap_or_else(MaskSpec::full)
+    }
+    
+    pub fn replacement(&self, pii_type: &PiiType) -> String {
+        self.rule(pii_type)
+            .and_then(|r| r.replacement.clone())
+            .unwrap_or_else(|| "[REDACTED]".to_string())
+    }
+
+    /// Drops detections the policy considers too uncertain to act on
+    pub fn filter(&self, detections: Vec<PiiDetection>) -> Vec<PiiDetection> {
+        detections
+            .into_iter()
+            .filter(|d| d.confidence >= self.threshold(&d.pii_type))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_str(contents: &str) -> Result<CompliancePolicy> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("policy.toml");
+        std::fs::write(&path, contents).unwrap();
+        CompliancePolicy::load(path.to_str().unwrap())
+    }
+
+    #[test]
+    fn unknown_action_is_rejected() {
+        let error = load_str("version = \"v1\"\n[rules.Email]\naction = \"shred\"\nseverity = \"High\"\n").unwrap_err();
+        assert!(format!("{:#}", error).contains("unknown variant `shred`"), "{:#}", error);
+    }
+
+    #[test]
+    fn blank_version_is_rejected() {
+        let error = load_str("version = \"  \"\n").unwrap_err();
+        assert!(format!("{:#}", error).contains("policy version must not be empty"), "{:#}", error);
+    }
+
+    #[test]
+    fn missing_replacement_falls_back_to_the_builtin_tag() {
+        let policy = load_str("version = \"v1\"\n[rules.Email]\naction = \"pseudonymize\"\nseverity = \"High\"\n").unwrap();
+        assert_eq!(policy.action(&PiiType::Email), PolicyAction::Pseudonymize);
+        assert_eq!(policy.replacement(&PiiType::Email), CompliancePolicy::default().replacement(&PiiType::Email));
+    }
+
+    #[test]
+    fn unlisted_types_and_purposes_fall_back_to_the_builtins() {
+        let policy = load_str("version = \"v1\"\n[rules.Email]\naction = \"mask\"\nseverity = \"Low\"\n").unwrap();
+        let builtin = CompliancePolicy::default();
+
+        assert_eq!(policy.version, "v1");
+        assert_eq!(policy.severity(&PiiType::Email), Severity::Low);
+        assert!(policy.rules[&PiiType::Email].mask.is_some());
+        assert_eq!(policy.rules.len(), builtin.rules.len());
+        assert_eq!(policy.action(&PiiType::SocialSecurityNumber), builtin.action(&PiiType::SocialSecurityNumber));
+        assert_eq!(policy.severity(&PiiType::SocialSecurityNumber), builtin.severity(&PiiType::SocialSecurityNumber));
+        assert!(policy.purpose("billing").is_some());
+    }
+
+    #[test]
+    fn outbound_redacts_what_the_inbound_policy_allows_or_masks() {
+        let mut policy = CompliancePolicy::default();
+        policy.guardrail.threshold = 0.5;
+        for (pii_type, action) in [
+            (PiiType::Email, PolicyAction::Allow),
+            (PiiType::CreditCardNumber, PolicyAction::Mask),
+            (PiiType::Name, PolicyAction::Pseudonymize),
+        ] {
+            let rule = policy.rules.get_mut(&pii_type).unwrap();
+            rule.action = action;
+            rule.threshold = Some(0.9);
+        }
+
+        let outbound = policy.outbound();
+        assert_eq!(outbound.action(&PiiType::Email), PolicyAction::Redact);
+        assert_eq!(outbound.action(&PiiType::CreditCardNumber), PolicyAction::Redact);
+        assert_eq!(outbound.action(&PiiType::Name), PolicyAction::Pseudonymize);
+        assert_eq!(outbound.threshold(&PiiType::Email), 0.5);
+    }
+}
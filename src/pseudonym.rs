@@ -0,0 +1,208 @@
+use crate::policy::{CompliancePolicy, PolicyAction};
+use crate::redaction::normalize;
+use crate::types::PiiType;
+use crate::utils::files;
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Used when a policy pseudonymizes and no `--pseudonyms` is given
+pub const DEFAULT_PSEUDONYM_PATH: &str = "data/pseudonyms.json";
+
+/// Secret keying the value digests pseudonym tables are built from
+pub const PSEUDONYM_KEY_ENV: &str = "PII_PSEUDONYM_KEY";
+
+/// Numbered placeholders assigned within one scope. Values are held only as
+/// keyed digests, so a table never contains the PII it stands in for.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct PseudonymTable {
+    /// Number assigned to each value, by hex digest
+    assigned: BTreeMap<String, usize>,
+    /// Last number handed out per type
+    last: BTreeMap<String, usize>,
+}
+
+/// Maps each distinct value within a scope to a numbered placeholder such as
+/// `[EMAIL_1]`, numbered per type in order of first appearance.
+///
+/// Values are identified by an HMAC of the scope, type and normalized value,
+/// so "John@Example.com" and "john@example.com" share a placeholder. Session
+/// and tenant tables are kept in a file, so numbers survive restarts and are
+/// shared by processes using the same file; document tables live only as
+/// long as the text they number.
+pub struct Pseudonymizer {
+    key: Vec<u8>,
+    path: Option<PathBuf>,
+    scopes: Mutex<BTreeMap<String, PseudonymTable>>,
+}
+
+impl Pseudonymizer {
+    /// Keeps every table in memory
+    pub fn new(key: &[u8]) -> Self {
+        Self {
+            key: key.to_vec(),
+            path: None,
+            scopes: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Keeps session and tenant tables in the file at `path`, creating it on
+    /// first write
+    pub fn open(path: &str, key: &[u8]) -> Result<Self> {
+        let scopes = read_tables(Path::new(path))?;
+
+        Ok(Self {
+            key: key.to_vec(),
+            path: Some(PathBuf::from(path)),
+            scopes: Mutex::new(scopes),
+        })
+    }
+
+    /// Keyed from `PII_PSEUDONYM_KEY`, or `None` when it is not set
+    pub fn from_env(path: Option<&str>) -> Result<Option<Self>> {
+        match std::env::var(PSEUDONYM_KEY_ENV) {
+            Ok(key) if !key.is_empty() => Self::open(path.unwrap_or(DEFAULT_PSEUDONYM_PATH), key.as_bytes()).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Keyed from the environment, failing if `policy` pseudonymizes but no
+    /// key is configured
+    pub fn for_policy(policy: &CompliancePolicy, path: Option<&str>) -> Result<Option<Self>> {
+        let pseudonymizer = Self::from_env(path)?;
+        if pseudonymizer.is_none() && policy.uses_action(PolicyAction::Pseudonymize) {
+            anyhow::bail!(
+                "policy {} pseudonymizes values but {} is not set",
+                policy.version, PSEUDONYM_KEY_ENV
+            );
+        }
+        Ok(pseudonymizer)
+    }
+
+    /// Placeholder for `value` within `scope`; `tag` is the rule's
+    /// replacement, e.g. "[EMAIL]" becomes "[EMAIL_1]"
+    pub fn pseudonym(&self, scope: &str, pii_type: &PiiType, value: &str, tag: &str) -> Result<String> {
+        let digest = self.digest(scope, pii_type, value);
+        let stem = tag.trim_start_matches('[').trim_end_matches(']');
+
+        let mut scopes = self.scopes.lock().unwrap();
+        if let Some(number) = scopes.get(scope).and_then(|table| table.assigned.get(&digest)) {
+            return Ok(format!("[{}_{}]", stem, number));
+        }
+
+        // Another process may have numbered values in this scope since the
+        // file was read; continue from the file under the lock
+        let path = self.path.as_deref().filter(|_| persists(scope));
+        let _lock = match path {
+            Some(path) => {
+                let lock = files::lock(path)?;
+                scopes.append(&mut read_tables(path)?);
+                Some(lock)
+            }
+            None => None,
+        };
+
+        let table = scopes.entry(scope.to_string()).or_default();
+        if let Some(number) = table.assigned.get(&digest) {
+            return Ok(format!("[{}_{}]", stem, number));
+        }
+        let before = table.clone();
+        let number = table.last.entry(format!("{:?}", pii_type)).or_insert(0);
+        *number += 1;
+        let number = *number;
+        table.assigned.insert(digest, number);
+
+        if let Some(path) = path {
+            if let Err(e) = save(path, &scopes) {
+                scopes.insert(scope.to_string(), before);
+                return Err(e);
+            }
+        }
+
+        Ok(format!("[{}_{}]", stem, number))
+    }
+
+    /// Forgets every placeholder assigned in `scope`
+    pub fn release(&self, scope: &str) {
+        self.scopes.lock().unwrap().remove(scope);
+    }
+
+    fn digest(&self, scope: &str, pii_type: &PiiType, value: &str) -> String {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.key).expect("HMAC accepts any key length");
+        for part in [scope, &format!("{:?}", pii_type), &normalize(pii_type, value)] {
+            mac.update(part.as_bytes());
+            mac.update(&[0]);
+        }
+        hex::encode(mac.finalize().into_bytes())
+    }
+}
+
+/// Document numbering restarts with every text, so only session and tenant
+/// tables are written to the file
+fn persists(scope: &str) -> bool {
+    !scope.starts_with("document:")
+}
+
+fn read_tables(path: &Path) -> Result<BTreeMap<String, PseudonymTable>> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read pseudonym tables {}", path.display()))?;
+    serde_json::from_str(&contents)
+        .with_context(|| format!("failed to parse pseudonym tables {}", path.display()))
+}
+
+fn save(path: &Path, scopes: &BTreeMap<String, PseudonymTable>) -> Result<()> {
+    let stored: BTreeMap<&String, &PseudonymTable> = scopes.iter()
+        .filter(|(scope, _)| persists(scope))
+        .collect();
+    files::write_atomic(path, &serde_json::to_string_pretty(&stored)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email(pseudonymizer: &Pseudonymizer, scope: &str, value: &str) -> String {
+        pseudonymizer.pseudonym(scope, &PiiType::Email, value, "[EMAIL]").unwrap()
+    }
+
+    #[test]
+    fn distinct_values_are_numbered_in_order_within_a_scope() {
+        let pseudonymizer = Pseudonymizer::new(b"key");
+
+        assert_eq!(email(&pseudonymizer, "session:s1", "john@example.com"), "[EMAIL_1]");
+        assert_eq!(email(&pseudonymizer, "session:s1", "jane@example.com"), "[EMAIL_2]");
+        assert_eq!(email(&pseudonymizer, "session:s1", "John@Example.com"), "[EMAIL_1]");
+        assert_eq!(
+            pseudonymizer.pseudonym("session:s1", &PiiType::Name, "John Doe", "[PERSON]").unwrap(),
+            "[PERSON_1]"
+        );
+        assert_eq!(email(&pseudonymizer, "session:s2", "jane@example.com"), "[EMAIL_1]");
+    }
+
+    #[test]
+    fn session_numbering_survives_a_restart_and_is_shared_through_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pseudonyms.json");
+        let path = path.to_str().unwrap();
+
+        let server = Pseudonymizer::open(path, b"key").unwrap();
+        let chatbot = Pseudonymizer::open(path, b"key").unwrap();
+        assert_eq!(email(&server, "session:s1", "john@example.com"), "[EMAIL_1]");
+        assert_eq!(email(&chatbot, "session:s1", "jane@example.com"), "[EMAIL_2]");
+        assert_eq!(email(&chatbot, "document:d1", "jane@example.com"), "[EMAIL_1]");
+
+        let restarted = Pseudonymizer::open(path, b"key").unwrap();
+        assert_eq!(email(&restarted, "session:s1", "jane@example.com"), "[EMAIL_2]");
+        assert_eq!(email(&restarted, "session:s1", "john@example.com"), "[EMAIL_1]");
+
+        let stored = std::fs::read_to_string(path).unwrap();
+        assert!(!stored.contains("example.com") && !stored.contains("document:"));
+    }
+}
@@ -0,0 +1,285 @@
+use crate::policy::{CompliancePolicy, MaskSpec, PolicyAction};
+use crate::generalization;
+use crate::hashing::KeyedHasher;
+use crate::pseudonym::Pseudonymizer;
+use crate::synthetic::Synthesizer;
+use crate::types::{PiiDetection, PiiType, RedactionSpan};
+use crate::vault::TokenVault;
+
+/// Output of a redaction pass: the rewritten text plus, for every replaced
+/// span, where it sat in the original and where its replacement sits now.
+#[derive(Debug, Clone, Default)]
+pub struct RedactionOutput {
+    pub text: String,
+    pub spans: Vec<RedactionSpan>,
+}
+
+/// Rewrites `text` by copying the untouched stretches between detections and
+/// emitting `replacement(detection)` in place of each detected span.
+///
+/// The output is built from the original text, so replacements of any length
+/// never shift later offsets. Detections are expected to be resolved (see
+/// `models::resolve_spans`); any span that overlaps an earlier one or does not
+/// fall on char boundaries is left untouched rather than corrupting the text.
+/// All offsets are byte offsets.
+pub fn rewrite<F>(text: &str, detections: &[PiiDetection], mut replacement: F) -> RedactionOutput
+where
+    F: FnMut(&PiiDetection) -> String,
+{
+    let mut sorted: Vec<&PiiDetection> = detections.iter().collect();
+    sorted.sort_by_key(|d| (d.start_pos, d.end_pos));
+
+    let mut output = String::with_capacity(text.len());
+    let mut spans = Vec::with_capacity(sorted.len());
+    let mut cursor = 0;
+
+    for detection in sorted {
+        let (start, end) = (detection.start_pos, detection.end_pos);
+        if start < cursor || start >= end || text.get(start..end).is_none() {
+            continue;
+        }
+
+        output.push_str(&text[cursor..start]);
+
+        let redacted_start = output.len();
+        output.push_str(&replacement(detection));
+
+        spans.push(RedactionSpan {
+            pii_type: detection.pii_type.clone(),
+            original_start: start,
+            original_end: end,
+            redacted_start,
+            redacted_end: output.len(),
+        });
+
+        cursor = end;
+    }
+
+    output.push_str(&text[cursor..]);
+
+    RedactionOutput { text: output, spans }
+}
+
+/// State some policy actions need beyond the policy itself
+#[derive(Clone, Copy, Default)]
+pub struct RedactionContext<'a> {
+    pub vault: Option<&'a TokenVault>,
+    pub pseudonymizer: Option<&'a Pseudonymizer>,
+    pub hasher: Option<&'a KeyedHasher>,
+    pub synthesizer: Option<&'a Synthesizer>,
+    /// Pseudonym table key, see `PseudonymConfig::scope_key`
+    pub scope: &'a str,
+}
+
+/// Applies each detection's policy action. Detections whose action is
+/// `allow` are left in place and produce no span. Actions whose state is
+/// missing from `context` (no vault, pseudonymizer, hash secret or synthesizer), or that fail, fall
+/// back to the replacement tag rather than leaking the value.
+pub fn apply_policy(
+    text: &str,
+    detections: &[PiiDetection],
+    policy: &CompliancePolicy,
+    context: &RedactionContext,
+) -> RedactionOutput {
+    let output = rewrite_with_policy(text, detections, policy, context);
+    
+    // Document-scoped numbering must not carry over to the next text
+    if let Some(pseudonymizer) = context.pseudonymizer {
+        if context.scope.starts_with("document:") {
+            pseudonymizer.release(context.scope);
+        }
+    }
+    
+    output
+}
+
+fn rewrite_with_policy(
+    text: &str,
+    detections: &[PiiDetection],
+    policy: &CompliancePolicy,
+    context: &RedactionContext,
+) -> RedactionOutput {
+    let today = chrono::Utc::now().date_naive();
+    let acted: Vec<PiiDetection> = detections
+        .iter()
+        .filter(|d| policy.action(&d.pii_type) != PolicyAction::Allow)
+        .cloned()
+        .collect();
+
+    rewrite(text, &acted, |detection| match policy.action(&detection.pii_type) {
+        PolicyAction::Redact => policy.replacement(&detection.pii_type),
+        PolicyAction::Mask => mask(&detection.value, &policy.mask(&detection.pii_type)),
+        PolicyAction::Hash => match context.hasher {
+            Some(hasher) => hasher.hash(&detection.pii_type, &detection.value),
+            None => policy.replacement(&detection.pii_type),
+        },
+        PolicyAction::Tokenize => context.vault
+            .map(|vault| vault.tokenize(&detection.pii_type, &detection.value))
+            .and_then(|token| token.map_err(|e| tracing::error!("tokenization failed: {:#}", e)).ok())
+            .unwrap_or_else(|| policy.replacement(&detection.pii_type)),
+        PolicyAction::Pseudonymize => context.pseudonymizer
+            .map(|pseudonymizer| pseudonymizer.pseudonym(
+                context.scope,
+                &detection.pii_type,
+                &detection.value,
+                &policy.replacement(&detection.pii_type),
+            ))
+            .and_then(|tag| tag.map_err(|e| tracing::error!("pseudonymization failed: {:#}", e)).ok())
+            .unwrap_or_else(|| policy.replacement(&detection.pii_type)),
+        PolicyAction::Synthesize => match context.synthesizer {
+            Some(synthesizer) => synthesizer.synthesize(detection),
+            None => policy.replacement(&detection.pii_type),
+        },
+        PolicyAction::Generalize => generalization::generalize(detection, &policy.generalization, today)
+            .unwrap_or_else(|| policy.replacement(&detection.pii_type)),
+        PolicyAction::Allow => detection.value.clone(),
+    })
+}
+
+/// Canonical form of a value, so trivially different spellings of the same
+/// value (case, separators) compare equal
+pub fn normalize(pii_type: &PiiType, value: &str) -> String {
+    match pii_type {
+        PiiType::Email | PiiType::Name | PiiType::Address => {
+            value.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+        }
+        PiiType::PhoneNumber
+        | PiiType::SocialSecurityNumber
+        | PiiType::IndividualTaxpayerNumber
+        | PiiType::EmployerIdentificationNumber
+        | PiiType::CreditCardNumber => value.chars().filter(|c| c.is_ascii_digit()).collect(),
+        _ => value.trim().to_lowercase(),
+    }
+}
+
+/// Format-preserving mask: keeps separators and length, and reveals only the
+/// characters `spec` asks for. If the kept characters would reveal the whole
+/// value, everything is masked instead.
+pub fn mask(value: &str, spec: &MaskSpec) -> String {
+    if spec.keep_domain {
+        if let Some((local, domain)) = value.rsplit_once('@') {
+            return format!("{}@{}", mask_chars(local, spec), domain);
+        }
+    }
+    mask_chars(value, spec)
+}
+
+fn mask_chars(value: &str, spec: &MaskSpec) -> String {
+    let total = value.chars().filter(|c| c.is_alphanumeric()).count();
+    let (keep_first, keep_last) = if spec.keep_first + spec.keep_last >= total {
+        (0, 0)
+    } else {
+        (spec.keep_first, spec.keep_last)
+    };
+
+    let mut seen = 0;
+    value
+        .chars()
+        .map(|c| {
+            if !c.is_alphanumeric() {
+                return c;
+            }
+            seen += 1;
+            if seen <= keep_first || seen > total - keep_last {
+                c
+            } else {
+                spec.mask_char
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detection(pii_type: PiiType, text: &str, start_pos: usize, end_pos: usize) -> PiiDetection {
+        PiiDetection {
+            pii_type,
+            confidence: 0.9,
+            start_pos,
+            end_pos,
+            value: text[start_pos..end_pos].to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rewrite_maps_offsets_for_replacements_of_any_length() {
+        let text = "mail a@b.co or call 555-123-4567.";
+        let detections = vec![
+            detection(PiiType::PhoneNumber, text, 20, 32),
+            detection(PiiType::Email, text, 5, 11),
+        ];
+        let output = rewrite(text, &detections, |d| match d.pii_type {
+            PiiType::Email => "[EMAIL_ADDRESS]".to_string(),
+            _ => "#".to_string(),
+        });
+
+        assert_eq!(output.text, "mail [EMAIL_ADDRESS] or call #.");
+        assert_eq!(output.spans.len(), 2);
+        for span in &output.spans {
+            let replaced = &output.text[span.redacted_start..span.redacted_end];
+            assert!(replaced == "[EMAIL_ADDRESS]" || replaced == "#");
+        }
+        assert_eq!((output.spans[0].original_start, output.spans[0].original_end), (5, 11));
+        assert_eq!((output.spans[1].redacted_start, output.spans[1].redacted_end), (29, 30));
+    }
+
+    #[test]
+    fn rewrite_handles_multibyte_text() {
+        let text = "José → a@b.co";
+        let start = text.find('a').unwrap();
+        let output = rewrite(text, &[detection(PiiType::Email, text, start, text.len())], |_| "[E]".to_string());
+
+        assert_eq!(output.text, "José → [E]");
+        assert_eq!(&output.text[output.spans[0].redacted_start..], "[E]");
+    }
+
+    #[test]
+    fn rewrite_skips_overlapping_and_misaligned_spans() {
+        let text = "héllo world";
+        let detections = vec![
+            detection(PiiType::Name, text, 0, 6),
+            detection(PiiType::Name, text, 3, 8),
+            PiiDetection { pii_type: PiiType::Name, start_pos: 2, end_pos: 4, ..Default::default() },
+            PiiDetection { pii_type: PiiType::Name, start_pos: 9, end_pos: 9, ..Default::default() },
+        ];
+        let output = rewrite(text, &detections, |_| "X".to_string());
+
+        assert_eq!(output.text, "X world");
+        assert_eq!(output.spans.len(), 1);
+    }
+
+    #[test]
+    fn rewrite_without_detections_returns_the_text() {
+        let output = rewrite("nothing here", &[], |_| unreachable!());
+        assert_eq!(output.text, "nothing here");
+        assert!(output.spans.is_empty());
+    }
+
+    #[test]
+    fn mask_preserves_separators_and_length() {
+        assert_eq!(mask("4111-1111-1111-1234", &MaskSpec::keep(0, 4)), "****-****-****-1234");
+        assert_eq!(mask("(555) 123-4567", &MaskSpec::keep(3, 2)), "(555) ***-**67");
+        assert_eq!(mask("123 45 6789", &MaskSpec::full()), "*** ** ****");
+    }
+
+    #[test]
+    fn mask_never_reveals_the_whole_value() {
+        assert_eq!(mask("12-34", &MaskSpec::keep(2, 2)), "**-**");
+        assert_eq!(mask("1234", &MaskSpec::keep(3, 3)), "****");
+    }
+
+    #[test]
+    fn mask_keeps_the_email_domain_when_asked() {
+        let spec = MaskSpec { keep_domain: true, mask_char: '#', ..MaskSpec::keep(1, 0) };
+        assert_eq!(mask("jane.doe@example.com", &spec), "j###.###@example.com");
+        assert_eq!(mask("not-an-email", &spec), "n##-##-#####");
+    }
+
+    #[test]
+    fn mask_counts_unicode_letters() {
+        assert_eq!(mask("José", &MaskSpec::keep(0, 1)), "***é");
+    }
+}
@@ -0,0 +1,274 @@
+use crate::policy::RetentionConfig;
+use crate::types::{ChatSession, RiskLevel};
+use anyhow::{Context, Result};
+use chrono::{Duration, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Used by `--session-db` when no path is given
+pub const DEFAULT_SESSION_DB_PATH: &str = "data/sessions.db";
+
+/// Sessions inactive for longer than this are evicted; matches the default
+/// `[retention] redacted_content_days`
+pub const DEFAULT_SESSION_TTL_HOURS: i64 = 90 * 24;
+
+/// Warns when `ttl` evicts sessions, and the violations recorded in them,
+/// before `retention` would delete their messages. Evicting early is
+/// stricter than the retention window, so it is allowed. Returns whether
+/// it warned.
+pub fn warn_if_shorter_than_retention(ttl: Duration, retention: &RetentionConfig) -> bool {
+    let shorter = ttl < Duration::days(retention.redacted_content_days);
+    if shorter {
+        tracing::warn!(
+            "a session TTL of {} hours is shorter than the {}-day [retention] redacted_content_days; \
+             sessions and their violations are deleted early",
+            ttl.num_hours(), retention.redacted_content_days
+        );
+    }
+    shorter
+}
+
+/// Where the chatbot agent keeps `ChatSession`s between messages.
+///
+/// A session expires once its `last_activity` is older than the store's
+/// TTL: `load` no longer returns it, and `evict_expired` deletes it.
+pub trait SessionStore: Send + Sync {
+    fn load(&self, session_id: &str) -> Result<Option<ChatSession>>;
+
+    /// Inserts or replaces the session. Only `last_activity` moves its
+    /// expiry, so saving a session does not by itself keep it alive.
+    fn save(&self, session: &ChatSession) -> Result<()>;
+
+    fn remove(&self, session_id: &str) -> Result<()>;
+
+    /// Live sessions, e.g. for a sweep over stored data
+    fn session_ids(&self) -> Result<Vec<String>>;
+
+    /// Deletes expired sessions and returns how many there were
+    fn evict_expired(&self) -> Result<usize>;
+
+    /// Risk level of a live session without loading its messages
+    fn risk_level(&self, session_id: &str) -> Result<Option<RiskLevel>> {
+        Ok(self.load(session_id)?.map(|session| session.risk_level))
+    }
+}
+
+/// Process-local store; sessions are lost on restart
+pub struct InMemorySessionStore {
+    ttl: Duration,
+    sessions: Mutex<HashMap<String, ChatSession>>,
+}
+
+impl InMemorySessionStore {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Default for InMemorySessionStore {
+    fn default() -> Self {
+        Self::new(Duration::hours(DEFAULT_SESSION_TTL_HOURS))
+    }
+}
+
+impl SessionStore for InMemorySessionStore {
+    fn load(&self, session_id: &str) -> Result<Option<ChatSession>> {
+        let cutoff = Utc::now() - self.ttl;
+        let sessions = self.sessions.lock().unwrap();
+        Ok(sessions.get(session_id)
+            .filter(|session| session.last_activity > cutoff)
+            .cloned())
+    }
+
+    fn save(&self, session: &ChatSession) -> Result<()> {
+        self.sessions.lock().unwrap().insert(session.session_id.clone(), session.clone());
+        Ok(())
+    }
+
+    fn remove(&self, session_id: &str) -> Result<()> {
+        self.sessions.lock().unwrap().remove(session_id);
+        Ok(())
+    }
+
+    fn session_ids(&self) -> Result<Vec<String>> {
+        let cutoff = Utc::now() - self.ttl;
+        let sessions = self.sessions.lock().unwrap();
+        Ok(sessions.iter()
+            .filter(|(_, session)| session.last_activity > cutoff)
+            .map(|(session_id, _)| session_id.clone())
+            .collect())
+    }
+
+    fn evict_expired(&self) -> Result<usize> {
+        let cutoff = Utc::now() - self.ttl;
+        let mut sessions = self.sessions.lock().unwrap();
+        let before = sessions.len();
+        sessions.retain(|_, session| session.last_activity > cutoff);
+        Ok(before - sessions.len())
+    }
+}
+
+/// SQLite-backed store, so session risk survives restarts. Each session is
+/// one row holding its JSON, with the risk level and last activity in their
+/// own columns.
+///
+/// The JSON is stored unencrypted, including raw message content until the
+/// retention sweep replaces it: protect the database file like the PII in it.
+pub struct SqliteSessionStore {
+    ttl: Duration,
+    conn: Mutex<Connection>,
+}
+
+impl SqliteSessionStore {
+    /// Opens or creates the database at `path`
+    pub fn open(path: &str, ttl: Duration) -> Result<Self> {
+        if let Some(dir) = Path::new(path).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+        let conn = Connection::open(path)
+            .with_context(|| format!("failed to open session database {}", path))?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS chat_sessions (
+                 session_id TEXT PRIMARY KEY,
+                 user_id TEXT NOT NULL,
+                 risk_level TEXT NOT NULL,
+                 session TEXT NOT NULL,
+                 updated_at INTEGER NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS chat_sessions_updated_at ON chat_sessions (updated_at);",
+        ).with_context(|| format!("{} is not a usable session database", path))?;
+
+        Ok(Self {
+            ttl,
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn cutoff(&self) -> i64 {
+        (Utc::now() - self.ttl).timestamp()
+    }
+}
+
+impl SessionStore for SqliteSessionStore {
+    fn load(&self, session_id: &str) -> Result<Option<ChatSession>> {
+        let conn = self.conn.lock().unwrap();
+        let json: Option<String> = conn.query_row(
+            "SELECT session FROM chat_sessions WHERE session_id = ?1 AND updated_at > ?2",
+            params![session_id, self.cutoff()],
+            |row| row.get(0),
+        ).optional()?;
+
+        json.map(|json| serde_json::from_str(&json)
+                .with_context(|| format!("stored session {} is corrupt", session_id)))
+            .transpose()
+    }
+
+    fn save(&self, session: &ChatSession) -> Result<()> {
+        let json = serde_json::to_string(session)?;
+        let risk_level = serde_json::to_string(&session.risk_level)?;
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO chat_sessions (session_id, user_id, risk_level, session, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT (session_id) DO UPDATE SET
+                 user_id = excluded.user_id,
+                 risk_level = excluded.risk_level,
+                 session = excluded.session,
+                 updated_at = excluded.updated_at",
+            params![session.session_id, session.user_id, risk_level, json, session.last_activity.timestamp()],
+        ).with_context(|| format!("failed to save session {}", session.session_id))?;
+        Ok(())
+    }
+
+    fn remove(&self, session_id: &str) -> Result<()> {
+        self.conn.lock().unwrap()
+            .execute("DELETE FROM chat_sessions WHERE session_id = ?1", params![session_id])?;
+        Ok(())
+    }
+
+    fn session_ids(&self) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare("SELECT session_id FROM chat_sessions WHERE updated_at > ?1")?;
+        let session_ids = statement.query_map(params![self.cutoff()], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(session_ids)
+    }
+
+    fn evict_expired(&self) -> Result<usize> {
+        let evicted = self.conn.lock().unwrap()
+            .execute("DELETE FROM chat_sessions WHERE updated_at <= ?1", params![self.cutoff()])?;
+        Ok(evicted)
+    }
+
+    fn risk_level(&self, session_id: &str) -> Result<Option<RiskLevel>> {
+        let conn = self.conn.lock().unwrap();
+        let risk_level: Option<String> = conn.query_row(
+            "SELECT risk_level FROM chat_sessions WHERE session_id = ?1 AND updated_at > ?2",
+            params![session_id, self.cutoff()],
+            |row| row.get(0),
+        ).optional()?;
+
+        risk_level.map(|risk_level| serde_json::from_str(&risk_level)
+                .with_context(|| format!("stored session {} has an unknown risk level", session_id)))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn session(session_id: &str, idle_hours: i64) -> ChatSession {
+        ChatSession {
+            session_id: session_id.to_string(),
+            user_id: "user".to_string(),
+            messages: vec![],
+            compliance_violations: vec![],
+            risk_level: RiskLevel::Safe,
+            redacted_content: HashMap::new(),
+            purpose: None,
+            last_activity: Utc::now() - Duration::hours(idle_hours),
+        }
+    }
+
+    fn expires_from_last_activity(store: &dyn SessionStore) {
+        store.save(&session("idle", 3)).unwrap();
+        store.save(&session("active", 1)).unwrap();
+
+        // A save without new activity, as the retention sweeper does
+        let mut idle = session("idle", 3);
+        idle.risk_level = RiskLevel::Low;
+        store.save(&idle).unwrap();
+
+        assert!(store.load("idle").unwrap().is_none());
+        assert!(store.load("active").unwrap().is_some());
+        assert_eq!(store.session_ids().unwrap(), vec!["active".to_string()]);
+        assert_eq!(store.evict_expired().unwrap(), 1);
+    }
+
+    #[test]
+    fn in_memory_sessions_expire_from_last_activity() {
+        expires_from_last_activity(&InMemorySessionStore::new(Duration::hours(2)));
+    }
+
+    #[test]
+    fn sqlite_sessions_expire_from_last_activity() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sessions.db");
+        expires_from_last_activity(&SqliteSessionStore::open(path.to_str().unwrap(), Duration::hours(2)).unwrap());
+    }
+
+    #[test]
+    fn ttl_shorter_than_the_retention_window_is_only_flagged() {
+        let retention = RetentionConfig::default();
+
+        assert!(!warn_if_shorter_than_retention(Duration::hours(DEFAULT_SESSION_TTL_HOURS), &retention));
+        assert!(warn_if_shorter_than_retention(Duration::hours(24), &retention));
+    }
+}
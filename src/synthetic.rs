@@ -0,0 +1,307 @@
+use crate::models::validators::{digits_only, luhn_valid};
+use crate::policy::{CompliancePolicy, PolicyAction};
+use crate::redaction::normalize;
+use crate::types::{CardNetwork, PiiDetection, PiiType};
+use anyhow::Result;
+use hmac::{Hmac, Mac};
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use regex::Regex;
+use sha2::Sha256;
+use std::sync::OnceLock;
+
+/// Secret keying the RNG fakes are drawn from
+pub const SYNTHETIC_KEY_ENV: &str = "PII_SYNTHETIC_KEY";
+
+const FIRST_NAMES: &[&str] = &[
+    "Alex", "Jordan", "Taylor", "Morgan", "Casey", "Riley", "Jamie", "Avery",
+    "Quinn", "Harper", "Rowan", "Emerson", "Dana", "Skyler", "Parker", "Reese",
+];
+
+const LAST_NAMES: &[&str] = &[
+    "Rivera", "Chen", "Okafor", "Novak", "Haddad", "Lindqvist", "Moreau", "Tanaka",
+    "Kowalski", "Adeyemi", "Fischer", "Delgado", "Sato", "Brennan", "Petrov", "Nakamura",
+];
+
+const STREETS: &[&str] = &["Maple", "Cedar", "Lakeview", "Hillcrest", "Willow", "Sunset", "Park", "Ridge"];
+const STREET_SUFFIXES: &[&str] = &["St", "Ave", "Rd", "Blvd", "Ln", "Dr"];
+const CITIES: &[&str] = &["Springfield", "Riverton", "Fairview", "Kingsport", "Greenville", "Lakewood"];
+
+/// Reserved for documentation (RFC 2606), so fake addresses never route
+const EMAIL_DOMAINS: &[&str] = &["example.com", "example.org", "example.net"];
+
+/// Realistic fakes for the `synthesize` action.
+///
+/// Each value's RNG is seeded with an HMAC of the `[synthetic] seed` and the
+/// normalized value, keyed by `PII_SYNTHETIC_KEY`, so a value always gets the
+/// same fake under the same key and seed. Without the key, fakes cannot be
+/// matched back to the values they replaced by trying candidate values.
+pub struct Synthesizer {
+    key: Vec<u8>,
+    seed: u64,
+}
+
+impl Synthesizer {
+    pub fn new(key: &[u8], seed: u64) -> Self {
+        Self { key: key.to_vec(), seed }
+    }
+
+    /// Keyed from `PII_SYNTHETIC_KEY` with the policy's `[synthetic]` seed,
+    /// failing if the policy synthesizes but no key is configured
+    pub fn for_policy(policy: &CompliancePolicy) -> Result<Option<Self>> {
+        let key = std::env::var(SYNTHETIC_KEY_ENV).ok().filter(|k| !k.is_empty());
+        match key {
+            Some(key) => Ok(Some(Self::new(key.as_bytes(), policy.synthetic.seed))),
+            None if policy.uses_action(PolicyAction::Synthesize) => anyhow::bail!(
+                "policy {} synthesizes values but {} is not set",
+                policy.version, SYNTHETIC_KEY_ENV
+            ),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns a fake of the same type and layout as `detection.value`.
+    ///
+    /// Emails use reserved domains, phone numbers the fictional 555-01xx
+    /// block, IPs the documentation ranges, cards the issuers' test-number
+    /// prefixes and SSNs an area number the SSA never issues, so none of
+    /// these can belong to anyone. ITINs, EINs, names, addresses and dates are
+    /// only structurally plausible and may coincide with real ones.
+    pub fn synthesize(&self, detection: &PiiDetection) -> String {
+        let mut rng = self.rng_for(&detection.pii_type, &detection.value);
+        let value = &detection.value;
+
+        match detection.pii_type {
+            PiiType::Email => fake_email(&mut rng),
+            PiiType::PhoneNumber => fake_phone(&mut rng, value),
+            PiiType::SocialSecurityNumber => fake_ssn(&mut rng, value),
+            PiiType::IndividualTaxpayerNumber => fake_itin(&mut rng, value),
+            PiiType::EmployerIdentificationNumber => fake_ein(&mut rng, value),
+            PiiType::CreditCardNumber => fake_card(&mut rng, value, detection.card_network),
+            PiiType::IpAddress => fake_ip(&mut rng, value),
+            PiiType::DateOfBirth => fake_date(&mut rng, value),
+            PiiType::Address => fake_address(&mut rng, value),
+            PiiType::Name => fake_name(&mut rng, value),
+            PiiType::Unknown => value.chars().map(|c| if c.is_alphanumeric() { 'X' } else { c }).collect(),
+        }
+    }
+
+    fn rng_for(&self, pii_type: &PiiType, value: &str) -> ChaCha8Rng {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.key).expect("HMAC accepts any key length");
+        mac.update(&self.seed.to_le_bytes());
+        mac.update(format!("{:?}", pii_type).as_bytes());
+        mac.update(&[0]);
+        mac.update(normalize(pii_type, value).as_bytes());
+        ChaCha8Rng::from_seed(mac.finalize().into_bytes().into())
+    }
+}
+
+fn pick<'a>(rng: &mut ChaCha8Rng, options: &[&'a str]) -> &'a str {
+    options.choose(rng).copied().unwrap_or_default()
+}
+
+fn random_digits(rng: &mut ChaCha8Rng, n: usize) -> String {
+    (0..n).map(|_| char::from(b'0' + rng.gen_range(0..10))).collect()
+}
+
+/// Writes `digits` into the digit positions of `template`, keeping its
+/// separators. Falls back to `fallback` when the digit counts differ.
+fn fill_digits(template: &str, digits: &str, fallback: impl FnOnce(&str) -> String) -> String {
+    if template.chars().filter(|c| c.is_ascii_digit()).count() != digits.len() {
+        return fallback(digits);
+    }
+
+    let mut digits = digits.chars();
+    template
+        .chars()
+        .map(|c| if c.is_ascii_digit() { digits.next().unwrap_or(c) } else { c })
+        .collect()
+}
+
+fn fake_email(rng: &mut ChaCha8Rng) -> String {
+    format!(
+        "{}.{}{}@{}",
+        pick(rng, FIRST_NAMES).to_lowercase(),
+        pick(rng, LAST_NAMES).to_lowercase(),
+        rng.gen_range(1..100),
+        pick(rng, EMAIL_DOMAINS)
+    )
+}
+
+/// 555-0100 through 555-0199 are reserved for fiction
+fn fake_phone(rng: &mut ChaCha8Rng, original: &str) -> String {
+    let original_digits = digits_only(original);
+    let local = format!("55501{}", random_digits(rng, 2));
+    let digits = match original_digits.len() {
+        7 => local,
+        11 if original_digits.starts_with('1') => format!("1{}{}", rng.gen_range(201..=989), local),
+        _ => format!("{}{}", rng.gen_range(201..=989), local),
+    };
+
+    fill_digits(original, &digits, |d| match d.len() {
+        7 => format!("{}-{}", &d[..3], &d[3..]),
+        _ => format!("({}) {}-{}", &d[d.len() - 10..][..3], &d[d.len() - 7..][..3], &d[d.len() - 4..]),
+    })
+}
+
+fn nine_digit_layout(d: &str) -> String {
+    format!("{}-{}-{}", &d[..3], &d[3..5], &d[5..])
+}
+
+/// Area 900-999 is never issued as an SSN, and groups 01-49 are outside
+/// every ITIN range, so the number belongs to no one
+fn fake_ssn(rng: &mut ChaCha8Rng, original: &str) -> String {
+    let digits = format!("9{}{:02}{:04}", random_digits(rng, 2), rng.gen_range(1..50), rng.gen_range(1..10000));
+    fill_digits(original, &digits, nine_digit_layout)
+}
+
+fn fake_itin(rng: &mut ChaCha8Rng, original: &str) -> String {
+    let digits = format!("9{}{}{:04}", random_digits(rng, 2), rng.gen_range(70..=88), rng.gen_range(1..10000));
+    fill_digits(original, &digits, nine_digit_layout)
+}
+
+fn fake_ein(rng: &mut ChaCha8Rng, original: &str) -> String {
+    let prefix = *[10, 12, 20, 27, 45, 46, 51, 52, 84, 91].choose(rng).unwrap_or(&12);
+    let digits = format!("{:02}{}", prefix, random_digits(rng, 7));
+    fill_digits(original, &digits, |d| format!("{}-{}", &d[..2], &d[2..]))
+}
+
+/// Keeps the original network and digit count (13-19, else the network's
+/// usual length), using the issuers' test prefixes and a valid Luhn check digit
+fn fake_card(rng: &mut ChaCha8Rng, original: &str, network: Option<CardNetwork>) -> String {
+    let (prefix, default_len) = match network {
+        Some(CardNetwork::AmericanExpress) => ("3782", 15),
+        Some(CardNetwork::Mastercard) => ("5555", 16),
+        Some(CardNetwork::Discover) => ("6011", 16),
+        Some(CardNetwork::Jcb) => ("3530", 16),
+        Some(CardNetwork::UnionPay) => ("6200", 16),
+        Some(CardNetwork::Visa) | None => ("4111", 16),
+    };
+    let len = match digits_only(original).len() {
+        len @ 13..=19 => len,
+        _ => default_len,
+    };
+
+    let mut digits = format!("{}{}", prefix, random_digits(rng, len - prefix.len() - 1));
+    let check = (0..10u8).map(|d| char::from(b'0' + d)).find(|d| luhn_valid(&format!("{}{}", digits, d)));
+    digits.push(check.unwrap_or('0'));
+
+    fill_digits(original, &digits, |d| {
+        d.as_bytes().chunks(4).map(|c| String::from_utf8_lossy(c).into_owned()).collect::<Vec<_>>().join("-")
+    })
+}
+
+/// IPv4 from the TEST-NET ranges (RFC 5737), IPv6 from 2001:db8::/32 (RFC 3849)
+fn fake_ip(rng: &mut ChaCha8Rng, original: &str) -> String {
+    if original.contains(':') {
+        return format!("2001:db8:{:x}::{:x}", rng.gen_range(0..0x10000u32), rng.gen_range(1..0x10000u32));
+    }
+    let net = pick(rng, &["192.0.2", "198.51.100", "203.0.113"]);
+    format!("{}.{}", net, rng.gen_range(1..255))
+}
+
+/// Keeps numeric layouts such as 05/15/1985 or 1985-05-15, otherwise
+/// falls back to MM/DD/YYYY
+fn fake_date(rng: &mut ChaCha8Rng, original: &str) -> String {
+    static ISO: OnceLock<Regex> = OnceLock::new();
+    static US: OnceLock<Regex> = OnceLock::new();
+    let iso = ISO.get_or_init(|| Regex::new(r"^(\d{4})([-/.])(\d{1,2})([-/.])(\d{1,2})$").unwrap());
+    let us = US.get_or_init(|| Regex::new(r"^(\d{1,2})([-/.])(\d{1,2})([-/.])(\d{2}|\d{4})$").unwrap());
+
+    let (year, month, day) = (rng.gen_range(1940..=2005), rng.gen_range(1..=12), rng.gen_range(1..=28));
+    let trimmed = original.trim();
+
+    if let Some(c) = iso.captures(trimmed) {
+        return format!("{}{}{:02}{}{:02}", year, &c[2], month, &c[4], day);
+    }
+    if let Some(c) = us.captures(trimmed) {
+        let year = if c[5].len() == 2 { format!("{:02}", year % 100) } else { year.to_string() };
+        return format!("{:02}{}{:02}{}{}", month, &c[2], day, &c[4], year);
+    }
+    format!("{:02}/{:02}/{}", month, day, year)
+}
+
+fn fake_address(rng: &mut ChaCha8Rng, original: &str) -> String {
+    let street = format!(
+        "{} {} {}",
+        rng.gen_range(10..9999),
+        pick(rng, STREETS),
+        pick(rng, STREET_SUFFIXES)
+    );
+    if original.contains(',') {
+        format!("{}, {}", street, pick(rng, CITIES))
+    } else {
+        street
+    }
+}
+
+/// Matches the word count: a lone token stays a first name
+fn fake_name(rng: &mut ChaCha8Rng, original: &str) -> String {
+    let first = pick(rng, FIRST_NAMES);
+    if original.split_whitespace().count() > 1 {
+        format!("{} {}", first, pick(rng, LAST_NAMES))
+    } else {
+        first.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::validators::{card_network, itin_valid, ssn_valid};
+
+    fn fake(synthesizer: &Synthesizer, pii_type: PiiType, value: &str) -> String {
+        let detection = PiiDetection { pii_type, value: value.to_string(), ..Default::default() };
+        synthesizer.synthesize(&detection)
+    }
+
+    #[test]
+    fn fakes_depend_on_the_key() {
+        let synthesizer = Synthesizer::new(b"key", 42);
+        let value = "jane.doe@corp.com";
+
+        assert_eq!(fake(&synthesizer, PiiType::Email, value), fake(&Synthesizer::new(b"key", 42), PiiType::Email, value));
+        let others: Vec<String> = (0..8u8)
+            .map(|k| fake(&Synthesizer::new(&[k], 42), PiiType::Email, value))
+            .collect();
+        assert!(others.iter().any(|other| *other != fake(&synthesizer, PiiType::Email, value)));
+    }
+
+    #[test]
+    fn fake_ssns_are_neither_ssns_nor_itins() {
+        let synthesizer = Synthesizer::new(b"key", 0);
+        for i in 0..200 {
+            let ssn = fake(&synthesizer, PiiType::SocialSecurityNumber, &format!("123-45-{:04}", i));
+            let digits = digits_only(&ssn);
+
+            assert_eq!(ssn.len(), 11);
+            assert!(digits.starts_with('9'), "{}", ssn);
+            assert!(!ssn_valid(&digits) && !itin_valid(&digits), "{}", ssn);
+        }
+    }
+
+    #[test]
+    fn fake_cards_keep_the_digit_count_and_network() {
+        let synthesizer = Synthesizer::new(b"key", 0);
+        for (original, network) in [
+            ("4111111111111111", CardNetwork::Visa),
+            ("4222222222222", CardNetwork::Visa),
+            ("4000 0000 0000 0000 006", CardNetwork::Visa),
+            ("3782-822463-10005", CardNetwork::AmericanExpress),
+        ] {
+            let detection = PiiDetection {
+                pii_type: PiiType::CreditCardNumber,
+                value: original.to_string(),
+                card_network: Some(network),
+                ..Default::default()
+            };
+            let card = synthesizer.synthesize(&detection);
+            let digits = digits_only(&card);
+
+            assert_eq!(digits.len(), digits_only(original).len(), "{}", card);
+            assert!(luhn_valid(&digits), "{}", card);
+            assert_eq!(card_network(&digits), Some(network), "{}", card);
+        }
+    }
+}
@@ -1,28 +1,62 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct PiiDetection {
     pub pii_type: PiiType,
+    #[serde(default)]
     pub confidence: f32,
     pub start_pos: usize,
     pub end_pos: usize,
     pub value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub card_network: Option<CardNetwork>,
+    /// Set when an LLM second pass reviewed the detection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification: Option<Verification>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    Keep,
+    Drop,
+}
+
+/// Outcome of an LLM review of one detection, kept for auditing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Verification {
+    pub verdict: Verdict,
+    pub rationale: String,
+    pub model: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, Default)]
 pub enum PiiType {
     Email,
     PhoneNumber,
     SocialSecurityNumber,
+    IndividualTaxpayerNumber,
+    EmployerIdentificationNumber,
     CreditCardNumber,
     IpAddress,
     DateOfBirth,
     Address,
     Name,
+    #[default]
     Unknown,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum CardNetwork {
+    Visa,
+    Mastercard,
+    AmericanExpress,
+    Discover,
+    Jcb,
+    UnionPay,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ComplianceResult {
     pub original_text: String,
@@ -30,6 +64,33 @@ pub struct ComplianceResult {
     pub detected_pii: Vec<PiiDetection>,
     pub compliance_score: f32,
     pub recommendations: Vec<String>,
+    #[serde(default)]
+    pub redaction_map: Vec<RedactionSpan>,
+    /// Detections dropped by LLM verification, with their rationale
+    #[serde(default)]
+    pub dismissed_pii: Vec<PiiDetection>,
+    /// Chat only: PII that this message completes together with earlier ones
+    #[serde(default)]
+    pub cross_message_pii: Vec<CrossMessageDetection>,
+}
+
+/// A detection found by rescanning several chat messages together
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrossMessageDetection {
+    /// Offsets are into the messages joined by single spaces, oldest first
+    pub detection: PiiDetection,
+    /// Every message the value spans, oldest first
+    pub message_ids: Vec<String>,
+}
+
+/// Byte offsets of one redacted span in the original and redacted text
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RedactionSpan {
+    pub pii_type: PiiType,
+    pub original_start: usize,
+    pub original_end: usize,
+    pub redacted_start: usize,
+    pub redacted_end: usize,
 }
 
 // New: Chatbot-specific types
@@ -50,6 +111,18 @@ pub struct ChatSession {
     pub messages: Vec<ChatMessage>,
     pub compliance_violations: Vec<ComplianceViolation>,
     pub risk_level: RiskLevel,
+    /// Redacted text of messages whose `content` is still raw, by message_id.
+    /// The retention sweep swaps it in once the raw content expires.
+    #[serde(default)]
+    pub redacted_content: HashMap<String, String>,
+    /// Declared purpose, a key of the policy's `purposes`; limits the PII
+    /// the session should collect and is the purpose consent is checked for
+    #[serde(default)]
+    pub purpose: Option<String>,
+    /// Last message or purpose change; the session store's TTL runs from
+    /// here, so saves by the retention sweeper do not keep a session alive
+    #[serde(default = "chrono::Utc::now")]
+    pub last_activity: chrono::DateTime<chrono::Utc>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -59,17 +132,44 @@ pub struct ComplianceViolation {
     pub message: String,
     pub timestamp: chrono::DateTime<chrono::Utc>,
     pub pii_detected: Vec<PiiDetection>,
+    /// Messages the exposed PII appeared in; several when it was split across messages
+    #[serde(default)]
+    pub message_ids: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum ViolationType {
     PiiExposure,
+    /// PII that appeared in a chatbot reply rather than a user message
+    BotDisclosure,
     DataRetention,
     ConsentMissing,
     UnauthorizedAccess,
     DataMinimization,
 }
 
+/// What the outbound guardrail did with a bot reply
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GuardrailDecision {
+    /// No PII found; delivered unchanged
+    Passed,
+    Blocked,
+    Redacted,
+    Regenerated,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuardrailResult {
+    pub decision: GuardrailDecision,
+    /// The reply to send to the user
+    pub delivered_text: String,
+    /// Scan of the original reply under the outbound policy
+    pub compliance_result: ComplianceResult,
+    /// LLM rewrites requested for this reply
+    pub regenerate_attempts: u32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum Severity {
     Low,
@@ -108,23 +208,18 @@ pub enum MessageType {
     ComplianceEnforcementRequest,
     ComplianceEnforcementResult,
     LlmReasoningResult,
+    PiiVerificationRequest,
+    PiiVerificationResult,
+    PiiExtractionRequest,
+    PiiExtractionResult,
     ChatMessageReceived,
     ChatSessionUpdate,
     ComplianceViolationAlert,
     Error,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct AgentContext {
     pub config: HashMap<String, String>,
     pub shared_memory: HashMap<String, serde_json::Value>,
-}
-
-impl Default for AgentContext {
-    fn default() -> Self {
-        Self {
-            config: HashMap::new(),
-            shared_memory: HashMap::new(),
-        }
-    }
 } 
\ No newline at end of file
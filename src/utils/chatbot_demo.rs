@@ -1,5 +1,6 @@
 use crate::types::{ChatMessage, ChatSession, RiskLevel};
 use chrono::Utc;
+use std::collections::HashMap;
 use uuid::Uuid;
 
 pub struct ChatbotDemoData;
@@ -22,6 +23,9 @@ impl ChatbotDemoData {
             
             // Scenario 4: Clean Conversation
             self.create_clean_scenario(),
+            
+            // Scenario 5: PII Split Across Messages
+            self.create_split_pii_scenario(),
         ]
     }
     
@@ -54,6 +58,14 @@ impl ChatbotDemoData {
                 content: "Sure, my email is john.doe@gmail.com and my phone is 555-123-4567".to_string(),
                 is_user_message: true,
             },
+            ChatMessage {
+                user_id: "bot_001".to_string(),
+                session_id: session_id.clone(),
+                message_id: Uuid::new_v4().to_string(),
+                timestamp: Utc::now(),
+                content: "Thanks! I found the account for john.doe@gmail.com. We'll text a code to 555-123-4567.".to_string(),
+                is_user_message: false,
+            },
         ];
         
         ChatSession {
@@ -62,6 +74,9 @@ impl ChatbotDemoData {
             messages,
             compliance_violations: vec![],
             risk_level: RiskLevel::Medium,
+            redacted_content: HashMap::new(),
+            purpose: Some("support".to_string()),
+            last_activity: Utc::now(),
         }
     }
     
@@ -102,6 +117,9 @@ impl ChatbotDemoData {
             messages,
             compliance_violations: vec![],
             risk_level: RiskLevel::Critical,
+            redacted_content: HashMap::new(),
+            purpose: Some("appointment".to_string()),
+            last_activity: Utc::now(),
         }
     }
     
@@ -142,6 +160,9 @@ impl ChatbotDemoData {
             messages,
             compliance_violations: vec![],
             risk_level: RiskLevel::High,
+            redacted_content: HashMap::new(),
+            purpose: Some("billing".to_string()),
+            last_activity: Utc::now(),
         }
     }
     
@@ -182,6 +203,48 @@ impl ChatbotDemoData {
             messages,
             compliance_violations: vec![],
             risk_level: RiskLevel::Safe,
+            redacted_content: HashMap::new(),
+            purpose: Some("weather".to_string()),
+            last_activity: Utc::now(),
         }
     }
-} 
\ No newline at end of file
+    
+    fn create_split_pii_scenario(&self) -> ChatSession {
+        let session_id = Uuid::new_v4().to_string();
+        let user_id = "user_321".to_string();
+        
+        let user_message = |content: &str| ChatMessage {
+            user_id: user_id.clone(),
+            session_id: session_id.clone(),
+            message_id: Uuid::new_v4().to_string(),
+            timestamp: Utc::now(),
+            content: content.to_string(),
+            is_user_message: true,
+        };
+        
+        let messages = vec![
+            user_message("I need to verify my identity, my SSN is 123"),
+            user_message("45-6789"),
+            user_message("and the card on file is 4111"),
+            user_message("1111 1111"),
+            user_message("1111"),
+        ];
+        
+        ChatSession {
+            session_id,
+            user_id,
+            messages,
+            compliance_violations: vec![],
+            risk_level: RiskLevel::Critical,
+            redacted_content: HashMap::new(),
+            purpose: Some("identity_verification".to_string()),
+            last_activity: Utc::now(),
+        }
+    }
+}
+
+impl Default for ChatbotDemoData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
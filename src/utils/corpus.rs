@@ -0,0 +1,360 @@
+use crate::types::{PiiDetection, PiiType, TrainingData};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CorpusFormat {
+    /// One JSON object per line with `text` and `spans` (or `pii_annotations`)
+    Jsonl,
+    /// Token-per-line CoNLL with BIO tags in the last column
+    Conll,
+    /// spaCy training JSON: `[[text, {"entities": [[start, end, label]]}]]`
+    /// or a list of `{"text", "ents": [{"start", "end", "label"}]}` docs
+    Spacy,
+}
+
+impl CorpusFormat {
+    /// Guesses the format from the file extension
+    pub fn from_path(path: &str) -> Option<Self> {
+        match Path::new(path).extension()?.to_str()? {
+            "jsonl" | "ndjson" => Some(Self::Jsonl),
+            "conll" | "bio" | "iob" => Some(Self::Conll),
+            "json" => Some(Self::Spacy),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for CorpusFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "jsonl" => Ok(Self::Jsonl),
+            "conll" | "bio" => Ok(Self::Conll),
+            "spacy" => Ok(Self::Spacy),
+            other => anyhow::bail!("unknown corpus format {:?} (expected jsonl, conll or spacy)", other),
+        }
+    }
+}
+
+/// An annotation that was rejected while loading
+#[derive(Debug, Clone, Serialize)]
+pub struct CorpusIssue {
+    pub location: String,
+    pub message: String,
+}
+
+impl fmt::Display for CorpusIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+/// Examples that passed validation, plus every annotation that did not.
+/// Rejected annotations are dropped from their example rather than trained on.
+#[derive(Debug, Clone, Default)]
+pub struct LoadedCorpus {
+    pub examples: Vec<TrainingData>,
+    pub issues: Vec<CorpusIssue>,
+}
+
+/// Maps corpus labels (our `PiiType` names or common NER/Presidio tags) to a type
+pub fn parse_label(label: &str) -> Option<PiiType> {
+    let normalized = label.trim().to_ascii_uppercase().replace(['-', ' '], "_");
+    let pii_type = match normalized.as_str() {
+        "EMAIL" | "EMAIL_ADDRESS" => PiiType::Email,
+        "PHONE" | "PHONE_NUMBER" | "PHONENUMBER" => PiiType::PhoneNumber,
+        "SSN" | "US_SSN" | "SOCIAL_SECURITY_NUMBER" | "SOCIALSECURITYNUMBER" => PiiType::SocialSecurityNumber,
+        "ITIN" | "US_ITIN" | "INDIVIDUALTAXPAYERNUMBER" => PiiType::IndividualTaxpayerNumber,
+        "EIN" | "US_EIN" | "EMPLOYERIDENTIFICATIONNUMBER" => PiiType::EmployerIdentificationNumber,
+        "CC" | "CREDIT_CARD" | "CREDIT_CARD_NUMBER" | "CREDITCARDNUMBER" => PiiType::CreditCardNumber,
+        "IP" | "IP_ADDRESS" | "IPADDRESS" => PiiType::IpAddress,
+        "DOB" | "DATE_OF_BIRTH" | "DATEOFBIRTH" => PiiType::DateOfBirth,
+        "ADDRESS" | "ADDR" | "STREET_ADDRESS" | "LOCATION" => PiiType::Address,
+        "NAME" | "PERSON" | "PER" => PiiType::Name,
+        "PII" | "UNKNOWN" => PiiType::Unknown,
+        _ => return None,
+    };
+    Some(pii_type)
+}
+
+/// Loads a corpus, inferring the format from the extension when not given
+pub fn load(path: &str, format: Option<CorpusFormat>) -> Result<LoadedCorpus> {
+    let format = format
+        .or_else(|| CorpusFormat::from_path(path))
+        .with_context(|| format!("cannot infer corpus format of {}; pass --format", path))?;
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read corpus {}", path))?;
+
+    match format {
+        CorpusFormat::Jsonl => parse_jsonl(path, &contents),
+        CorpusFormat::Conll => Ok(parse_conll(path, &contents)),
+        CorpusFormat::Spacy => parse_spacy(path, &contents),
+    }
+}
+
+/// Re-checks already constructed examples, e.g. the built-in demo corpus
+pub fn validate(examples: Vec<TrainingData>, source: &str) -> LoadedCorpus {
+    let mut corpus = LoadedCorpus::default();
+    for (i, example) in examples.into_iter().enumerate() {
+        let spans = example.pii_annotations.into_iter().map(RawSpan::from).collect();
+        let location = format!("{}#{}", source, i + 1);
+        corpus.push(example.text, spans, &location);
+    }
+    corpus
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RawSpan {
+    #[serde(alias = "start_pos")]
+    start: usize,
+    #[serde(alias = "end_pos")]
+    end: usize,
+    #[serde(alias = "pii_type", alias = "type")]
+    label: String,
+    #[serde(default)]
+    value: Option<String>,
+}
+
+impl From<PiiDetection> for RawSpan {
+    fn from(detection: PiiDetection) -> Self {
+        Self {
+            start: detection.start_pos,
+            end: detection.end_pos,
+            label: format!("{:?}", detection.pii_type),
+            value: Some(detection.value),
+        }
+    }
+}
+
+impl LoadedCorpus {
+    /// Validates each span against `text` and records the example with the
+    /// spans that survive
+    fn push(&mut self, text: String, spans: Vec<RawSpan>, location: &str) {
+        let mut pii_annotations = vec![];
+
+        for span in spans {
+            let label = span.label.clone();
+            let mut reject = |message: String| {
+                self.issues.push(CorpusIssue {
+                    location: location.to_string(),
+                    message: format!("{} span {}..{}: {}", label, span.start, span.end, message),
+                });
+            };
+
+            // Point at where the annotated value actually is, if it says
+            let hint = span.value.as_deref()
+                .and_then(|value| nearest_occurrence(&text, value, span.start)
+                    .map(|start| format!("; {:?} is at {}..{}", value, start, start + value.len())))
+                .unwrap_or_default();
+
+            let Some(pii_type) = parse_label(&span.label) else {
+                reject("unknown label".to_string());
+                continue;
+            };
+            let Some(covered) = text.get(span.start..span.end).filter(|_| span.start < span.end) else {
+                reject(format!("out of bounds or not on a char boundary (text is {} bytes){}", text.len(), hint));
+                continue;
+            };
+
+            if let Some(value) = &span.value {
+                if covered != value {
+                    reject(format!("covers {:?} but value is {:?}{}", covered, value, hint));
+                    continue;
+                }
+            }
+
+            pii_annotations.push(PiiDetection {
+                pii_type,
+                confidence: 1.0,
+                start_pos: span.start,
+                end_pos: span.end,
+                value: covered.to_string(),
+                ..Default::default()
+            });
+        }
+
+        self.examples.push(TrainingData { text, pii_annotations });
+    }
+}
+
+fn nearest_occurrence(text: &str, value: &str, near: usize) -> Option<usize> {
+    if value.is_empty() {
+        return None;
+    }
+    text.match_indices(value).map(|(i, _)| i).min_by_key(|i| i.abs_diff(near))
+}
+
+#[derive(Deserialize)]
+struct JsonlRecord {
+    text: String,
+    #[serde(default)]
+    spans: Vec<RawSpan>,
+    #[serde(default)]
+    pii_annotations: Vec<RawSpan>,
+}
+
+fn parse_jsonl(path: &str, contents: &str) -> Result<LoadedCorpus> {
+    let mut corpus = LoadedCorpus::default();
+
+    for (i, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let location = format!("{}:{}", path, i + 1);
+        let record: JsonlRecord = serde_json::from_str(line)
+            .with_context(|| format!("{}: invalid JSONL record", location))?;
+
+        let mut spans = record.spans;
+        spans.extend(record.pii_annotations);
+        corpus.push(record.text, spans, &location);
+    }
+
+    Ok(corpus)
+}
+
+/// Rebuilds each sentence by joining tokens with single spaces, so spans are
+/// aligned by construction; only tag sequence problems are reported
+fn parse_conll(path: &str, contents: &str) -> LoadedCorpus {
+    let mut corpus = LoadedCorpus::default();
+    let mut text = String::new();
+    let mut spans: Vec<RawSpan> = vec![];
+    let mut sentence_line = 1;
+
+    let flush = |text: &mut String, spans: &mut Vec<RawSpan>, line: usize, corpus: &mut LoadedCorpus| {
+        if !text.is_empty() {
+            corpus.push(std::mem::take(text), std::mem::take(spans), &format!("{}:{}", path, line));
+        }
+    };
+
+    for (i, line) in contents.lines().enumerate() {
+        let line_no = i + 1;
+        let columns: Vec<&str> = line.split_whitespace().collect();
+
+        if columns.is_empty() || columns[0] == "-DOCSTART-" {
+            flush(&mut text, &mut spans, sentence_line, &mut corpus);
+            sentence_line = line_no + 1;
+            continue;
+        }
+
+        let token = columns[0];
+        let tag = if columns.len() > 1 { columns[columns.len() - 1] } else { "O" };
+
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        let start = text.len();
+        text.push_str(token);
+        let end = text.len();
+
+        match tag.split_once('-') {
+            Some(("I", label)) if spans.last().is_some_and(|s| s.label == label && s.end + 1 == start) => {
+                if let Some(last) = spans.last_mut() {
+                    last.end = end;
+                }
+            }
+            Some((prefix @ ("B" | "I"), label)) => {
+                if prefix == "I" {
+                    corpus.issues.push(CorpusIssue {
+                        location: format!("{}:{}", path, line_no),
+                        message: format!("I-{} without a preceding B-{}; starting a new span", label, label),
+                    });
+                }
+                spans.push(RawSpan { start, end, label: label.to_string(), value: None });
+            }
+            _ if tag == "O" => {}
+            _ => corpus.issues.push(CorpusIssue {
+                location: format!("{}:{}", path, line_no),
+                message: format!("unrecognized BIO tag {:?}", tag),
+            }),
+        }
+    }
+    flush(&mut text, &mut spans, sentence_line, &mut corpus);
+
+    corpus
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SpacyRecord {
+    /// `["text", {"entities": [[start, end, "LABEL"]]}]`
+    Training(String, SpacyAnnotations),
+    /// `{"text": "...", "ents": [{"start": 0, "end": 4, "label": "PERSON"}]}`
+    Doc { text: String, ents: Vec<RawSpan> },
+}
+
+#[derive(Deserialize)]
+struct SpacyAnnotations {
+    #[serde(default)]
+    entities: Vec<(usize, usize, String)>,
+}
+
+fn parse_spacy(path: &str, contents: &str) -> Result<LoadedCorpus> {
+    let records: Vec<SpacyRecord> = serde_json::from_str(contents)
+        .with_context(|| format!("{} is not spaCy training JSON", path))?;
+    let mut corpus = LoadedCorpus::default();
+
+    for (i, record) in records.into_iter().enumerate() {
+        let location = format!("{}[{}]", path, i);
+        let (text, spans) = match record {
+            SpacyRecord::Training(text, annotations) => {
+                let spans = annotations.entities.into_iter()
+                    .map(|(start, end, label)| RawSpan { start, end, label, value: None })
+                    .collect();
+                (text, spans)
+            }
+            SpacyRecord::Doc { text, ents } => (text, ents),
+        };
+        let spans = spans.into_iter().map(|span| char_span_to_bytes(&text, span)).collect();
+        corpus.push(text, spans, &location);
+    }
+
+    Ok(corpus)
+}
+
+/// spaCy offsets count characters; spans are validated as byte offsets.
+/// Offsets past the end of the text are kept so validation reports them.
+fn char_span_to_bytes(text: &str, span: RawSpan) -> RawSpan {
+    let byte_offset = |chars: usize| text.char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(text.len()))
+        .nth(chars);
+
+    match (byte_offset(span.start), byte_offset(span.end)) {
+        (Some(start), Some(end)) => RawSpan { start, end, ..span },
+        _ => span,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spacy_offsets_are_characters() {
+        let json = r#"[
+            ["Café: José Núñez, jose@example.com", {"entities": [[6, 16, "PERSON"], [18, 34, "EMAIL"]]}],
+            {"text": "Née à Zürich: Anaïs", "ents": [{"start": 14, "end": 19, "label": "PERSON"}]}
+        ]"#;
+        let corpus = parse_spacy("test.json", json).unwrap();
+
+        assert!(corpus.issues.is_empty(), "{:?}", corpus.issues);
+        let values: Vec<&str> = corpus.examples.iter()
+            .flat_map(|e| e.pii_annotations.iter().map(|a| a.value.as_str()))
+            .collect();
+        assert_eq!(values, vec!["José Núñez", "jose@example.com", "Anaïs"]);
+    }
+
+    #[test]
+    fn spacy_offsets_past_the_end_are_reported() {
+        let json = r#"[["Zoë", {"entities": [[0, 9, "PERSON"]]}]]"#;
+        let corpus = parse_spacy("test.json", json).unwrap();
+
+        assert_eq!(corpus.issues.len(), 1);
+        assert!(corpus.examples[0].pii_annotations.is_empty());
+    }
+}
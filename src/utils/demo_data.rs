@@ -10,139 +10,114 @@ impl DemoData {
     pub fn get_training_data(&self) -> Vec<TrainingData> {
         vec![
             // Email examples
-            TrainingData {
-                text: "Contact us at john.doe@example.com for support".to_string(),
-                pii_annotations: vec![
-                    PiiDetection {
-                        pii_type: PiiType::Email,
-                        confidence: 0.95,
-                        start_pos: 13,
-                        end_pos: 33,
-                        value: "john.doe@example.com".to_string(),
-                    }
-                ],
-            },
-            TrainingData {
-                text: "My email is alice.smith@gmail.com".to_string(),
-                pii_annotations: vec![
-                    PiiDetection {
-                        pii_type: PiiType::Email,
-                        confidence: 0.95,
-                        start_pos: 12,
-                        end_pos: 32,
-                        value: "alice.smith@gmail.com".to_string(),
-                    }
-                ],
-            },
+            Self::example("Contact us at john.doe@example.com for support", &[
+                (PiiType::Email, "john.doe@example.com"),
+            ]),
+            Self::example("My email is alice.smith@gmail.com", &[
+                (PiiType::Email, "alice.smith@gmail.com"),
+            ]),
             
             // Phone number examples
-            TrainingData {
-                text: "Call me at 555-123-4567".to_string(),
-                pii_annotations: vec![
-                    PiiDetection {
-                        pii_type: PiiType::PhoneNumber,
-                        confidence: 0.9,
-                        start_pos: 12,
-                        end_pos: 25,
-                        value: "555-123-4567".to_string(),
-                    }
-                ],
-            },
-            TrainingData {
-                text: "Phone: (555) 987-6543".to_string(),
-                pii_annotations: vec![
-                    PiiDetection {
-                        pii_type: PiiType::PhoneNumber,
-                        confidence: 0.9,
-                        start_pos: 7,
-                        end_pos: 22,
-                        value: "(555) 987-6543".to_string(),
-                    }
-                ],
-            },
+            Self::example("Call me at 555-123-4567", &[
+                (PiiType::PhoneNumber, "555-123-4567"),
+            ]),
+            Self::example("Phone: (555) 987-6543", &[
+                (PiiType::PhoneNumber, "(555) 987-6543"),
+            ]),
             
             // SSN examples
-            TrainingData {
-                text: "SSN: 123-45-6789".to_string(),
-                pii_annotations: vec![
-                    PiiDetection {
-                        pii_type: PiiType::SocialSecurityNumber,
-                        confidence: 0.95,
-                        start_pos: 5,
-                        end_pos: 18,
-                        value: "123-45-6789".to_string(),
-                    }
-                ],
-            },
+            Self::example("SSN: 123-45-6789", &[
+                (PiiType::SocialSecurityNumber, "123-45-6789"),
+            ]),
             
             // Credit card examples
-            TrainingData {
-                text: "Card: 1234-5678-9012-3456".to_string(),
-                pii_annotations: vec![
-                    PiiDetection {
-                        pii_type: PiiType::CreditCardNumber,
-                        confidence: 0.9,
-                        start_pos: 6,
-                        end_pos: 27,
-                        value: "1234-5678-9012-3456".to_string(),
-                    }
-                ],
-            },
+            Self::example("Card: 5555-5555-5555-4444", &[
+                (PiiType::CreditCardNumber, "5555-5555-5555-4444"),
+            ]),
             
             // IP address examples
-            TrainingData {
-                text: "Server IP: 192.168.1.100".to_string(),
-                pii_annotations: vec![
-                    PiiDetection {
-                        pii_type: PiiType::IpAddress,
-                        confidence: 0.9,
-                        start_pos: 12,
-                        end_pos: 24,
-                        value: "192.168.1.100".to_string(),
-                    }
-                ],
-            },
+            Self::example("Server IP: 192.168.1.100", &[
+                (PiiType::IpAddress, "192.168.1.100"),
+            ]),
             
             // Mixed PII examples
-            TrainingData {
-                text: "Contact John Doe at john.doe@company.com or 555-123-4567".to_string(),
-                pii_annotations: vec![
-                    PiiDetection {
-                        pii_type: PiiType::Name,
-                        confidence: 0.8,
-                        start_pos: 8,
-                        end_pos: 17,
-                        value: "John Doe".to_string(),
-                    },
-                    PiiDetection {
-                        pii_type: PiiType::Email,
-                        confidence: 0.95,
-                        start_pos: 25,
-                        end_pos: 45,
-                        value: "john.doe@company.com".to_string(),
-                    },
-                    PiiDetection {
-                        pii_type: PiiType::PhoneNumber,
-                        confidence: 0.9,
-                        start_pos: 49,
-                        end_pos: 62,
-                        value: "555-123-4567".to_string(),
-                    }
-                ],
-            },
+            Self::example("Contact John Doe at john.doe@company.com or 555-123-4567", &[
+                (PiiType::Name, "John Doe"),
+                (PiiType::Email, "john.doe@company.com"),
+                (PiiType::PhoneNumber, "555-123-4567"),
+            ]),
+            
+            // Names, addresses and dates of birth for the token tagger
+            Self::example("My name is Jane Smith and I was born on 05/15/1985", &[
+                (PiiType::Name, "Jane Smith"),
+                (PiiType::DateOfBirth, "05/15/1985"),
+            ]),
+            Self::example("Patient Robert Brown, DOB 11/02/1974, lives at 42 Oak Street, Denver", &[
+                (PiiType::Name, "Robert Brown"),
+                (PiiType::DateOfBirth, "11/02/1974"),
+                (PiiType::Address, "42 Oak Street, Denver"),
+            ]),
+            Self::example("Please ship it to 1600 Pennsylvania Avenue, Washington", &[
+                (PiiType::Address, "1600 Pennsylvania Avenue, Washington"),
+            ]),
+            Self::example("Hi, this is Maria Garcia calling about my order", &[
+                (PiiType::Name, "Maria Garcia"),
+            ]),
+            Self::example("Date of birth: 1990-07-23", &[
+                (PiiType::DateOfBirth, "1990-07-23"),
+            ]),
+            Self::example("I live at 221 Baker Street, London", &[
+                (PiiType::Address, "221 Baker Street, London"),
+            ]),
+            Self::example("Customer David Miller was born 03/30/1962", &[
+                (PiiType::Name, "David Miller"),
+                (PiiType::DateOfBirth, "03/30/1962"),
+            ]),
+            Self::example("Send the documents to 77 Sunset Blvd, Los Angeles", &[
+                (PiiType::Address, "77 Sunset Blvd, Los Angeles"),
+            ]),
+            Self::example("Born on 12/01/2000, Sarah Johnson moved to 9 Elm Road, Austin", &[
+                (PiiType::DateOfBirth, "12/01/2000"),
+                (PiiType::Name, "Sarah Johnson"),
+                (PiiType::Address, "9 Elm Road, Austin"),
+            ]),
+            Self::example("Ask Michael Lee for the quarterly report", &[
+                (PiiType::Name, "Michael Lee"),
+            ]),
+            Self::example("Order 4471 shipped on 02/14/2023 from the Main warehouse", &[]),
+            Self::example("The meeting is on Monday in the North conference room", &[]),
             
             // Clean text examples
-            TrainingData {
-                text: "This is a clean text with no PII".to_string(),
-                pii_annotations: vec![],
-            },
-            TrainingData {
-                text: "General information about our services".to_string(),
-                pii_annotations: vec![],
-            },
+            Self::example("This is a clean text with no PII", &[]),
+            Self::example("General information about our services", &[]),
         ]
     }
     
+    /// Builds a training example, locating each annotated value in order so
+    /// offsets always match the text
+    fn example(text: &str, annotations: &[(PiiType, &str)]) -> TrainingData {
+        let mut search_from = 0;
+        let pii_annotations = annotations.iter().map(|(pii_type, value)| {
+            let start_pos = search_from + text[search_from..].find(value)
+                .unwrap_or_else(|| panic!("{:?} not found in {:?}", value, text));
+            search_from = start_pos + value.len();
+            
+            PiiDetection {
+                pii_type: pii_type.clone(),
+                confidence: 1.0,
+                start_pos,
+                end_pos: search_from,
+                value: value.to_string(),
+                ..Default::default()
+            }
+        }).collect();
+        
+        TrainingData {
+            text: text.to_string(),
+            pii_annotations,
+        }
+    }
+    
     pub fn get_demo_texts(&self) -> Vec<String> {
         vec![
             "Contact Alice Johnson at alice.johnson@company.com or call 555-987-6543 for support".to_string(),
@@ -153,4 +128,10 @@ impl DemoData {
             "Meeting notes: Discuss project timeline with team@company.com".to_string(),
         ]
     }
-} 
\ No newline at end of file
+}
+
+impl Default for DemoData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
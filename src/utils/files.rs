@@ -0,0 +1,53 @@
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+/// Exclusive advisory lock on a `<file>.lock` sidecar, held until dropped.
+/// The sidecar is locked rather than the file itself because `write_atomic`
+/// replaces the file, and a lock on the old inode would not cover the new one.
+pub struct FileLock {
+    _file: File,
+}
+
+/// Blocks until this process holds the lock for `path`
+pub fn lock(path: &Path) -> Result<FileLock> {
+    create_parent(path)?;
+    let lock_path = sidecar(path, "lock");
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("failed to open {}", lock_path.display()))?;
+    file.lock().with_context(|| format!("failed to lock {}", lock_path.display()))?;
+
+    Ok(FileLock { _file: file })
+}
+
+/// Writes through a temporary file and renames it over `path`, so readers
+/// and crashes never see a truncated file
+pub fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    create_parent(path)?;
+    let tmp = sidecar(path, &format!("{}.tmp", std::process::id()));
+    std::fs::write(&tmp, contents)
+        .with_context(|| format!("failed to write {}", tmp.display()))?;
+    std::fs::rename(&tmp, path)
+        .with_context(|| format!("failed to replace {}", path.display()))?;
+
+    Ok(())
+}
+
+fn create_parent(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    Ok(())
+}
+
+fn sidecar(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
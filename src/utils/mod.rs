@@ -1,5 +1,7 @@
 pub mod demo_data;
 pub mod chatbot_demo;
+pub mod corpus;
+pub mod files;
 
 pub use demo_data::DemoData;
 pub use chatbot_demo::ChatbotDemoData; 
\ No newline at end of file
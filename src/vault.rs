@@ -0,0 +1,274 @@
+use crate::access::AccessKey;
+use crate::policy::{CompliancePolicy, PolicyAction};
+use crate::types::PiiType;
+use crate::utils::files;
+use anyhow::{Context, Result};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Used when a policy tokenizes and no `--vault` is given
+pub const DEFAULT_VAULT_PATH: &str = "data/token_vault.json";
+
+/// Hex-encoded 32-byte key that encrypts vault entries and derives tokens
+pub const VAULT_KEY_ENV: &str = "PII_VAULT_KEY";
+
+/// Shared secret callers must present to detokenize; unset disables detokenization
+pub const VAULT_ACCESS_KEY_ENV: &str = "PII_VAULT_ACCESS_KEY";
+
+const VAULT_FORMAT_VERSION: u32 = 1;
+
+/// Hex characters of the keyed digest kept in each token
+const TOKEN_DIGEST_CHARS: usize = 24;
+
+#[derive(Debug, thiserror::Error)]
+pub enum DetokenizeError {
+    #[error("detokenization is disabled: {} is not set", VAULT_ACCESS_KEY_ENV)]
+    Disabled,
+    #[error("access key rejected")]
+    Unauthorized,
+    #[error("unknown token {0}")]
+    UnknownToken(String),
+    #[error("vault entry for {0} failed to decrypt")]
+    Corrupt(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct VaultEntry {
+    pii_type: PiiType,
+    nonce: String,
+    ciphertext: String,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VaultFile {
+    format_version: u32,
+    entries: BTreeMap<String, VaultEntry>,
+}
+
+/// Encrypted local store mapping opaque tokens back to the values they replaced.
+///
+/// Tokens are derived from a keyed digest of the type and value, so the same
+/// value always gets the same token and tokens reveal nothing without the key.
+/// Each value is sealed with ChaCha20-Poly1305 under its own nonce, with the
+/// token as associated data so entries cannot be swapped between tokens.
+///
+/// Several processes may share one vault file: new entries are merged into
+/// the file under a lock, and unknown tokens are looked up on disk again.
+pub struct TokenVault {
+    path: PathBuf,
+    cipher: ChaCha20Poly1305,
+    token_key: [u8; 32],
+    access_key: Option<AccessKey>,
+    entries: Mutex<BTreeMap<String, VaultEntry>>,
+}
+
+impl TokenVault {
+    /// Opens the vault at `path`, creating it on first write
+    pub fn open(path: &str, key: [u8; 32]) -> Result<Self> {
+        let entries = read_entries(Path::new(path))?;
+
+        // Separate keys for encryption and token derivation
+        let token_key: [u8; 32] = Sha256::new()
+            .chain_update(b"pii-vault-token-key")
+            .chain_update(key)
+            .finalize()
+            .into();
+
+        Ok(Self {
+            path: PathBuf::from(path),
+            cipher: ChaCha20Poly1305::new(Key::from_slice(&key)),
+            token_key,
+            access_key: None,
+            entries: Mutex::new(entries),
+        })
+    }
+
+    /// Opens the vault with the key from `PII_VAULT_KEY`, or returns `None`
+    /// when no key is configured
+    pub fn from_env(path: Option<&str>) -> Result<Option<Self>> {
+        let Ok(key) = std::env::var(VAULT_KEY_ENV) else {
+            return Ok(None);
+        };
+        let key: [u8; 32] = hex::decode(key.trim())
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .with_context(|| format!("{} must be 64 hex characters (32 bytes)", VAULT_KEY_ENV))?;
+
+        let mut vault = Self::open(path.unwrap_or(DEFAULT_VAULT_PATH), key)?;
+        vault.access_key = AccessKey::from_env(VAULT_ACCESS_KEY_ENV);
+        Ok(Some(vault))
+    }
+
+    /// Opens the vault from the environment, failing if `policy` tokenizes
+    /// but no vault key is configured
+    pub fn for_policy(policy: &CompliancePolicy, path: Option<&str>) -> Result<Option<Self>> {
+        let vault = Self::from_env(path)?;
+        if vault.is_none() && policy.uses_action(PolicyAction::Tokenize) {
+            anyhow::bail!(
+                "policy {} tokenizes values but no token vault is configured; set {}",
+                policy.version, VAULT_KEY_ENV
+            );
+        }
+        Ok(vault)
+    }
+
+    pub fn with_access_key(mut self, access_key: &str) -> Self {
+        self.access_key = Some(AccessKey::new(access_key));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the stable token for `value`, storing the value on first use
+    pub fn tokenize(&self, pii_type: &PiiType, value: &str) -> Result<String> {
+        let token = self.token_for(pii_type, value);
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.contains_key(&token) {
+            return Ok(token);
+        }
+
+        // Another process may have added entries, or this very token, since
+        // the vault was read; merge under the lock so none are overwritten
+        let _lock = files::lock(&self.path)?;
+        entries.append(&mut read_entries(&self.path)?);
+        if entries.contains_key(&token) {
+            return Ok(token);
+        }
+
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self.cipher
+            .encrypt(&nonce, Payload { msg: value.as_bytes(), aad: token.as_bytes() })
+            .map_err(|_| anyhow::anyhow!("failed to encrypt value for {}", token))?;
+
+        entries.insert(token.clone(), VaultEntry {
+            pii_type: pii_type.clone(),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+            created_at: chrono::Utc::now(),
+        });
+
+        if let Err(e) = self.save(&entries) {
+            entries.remove(&token);
+            return Err(e);
+        }
+
+        Ok(token)
+    }
+
+    /// Returns the original value behind `token` to a caller holding the access key
+    pub fn detokenize(&self, token: &str, access_key: &str) -> Result<String, DetokenizeError> {
+        let expected = self.access_key.as_ref().ok_or(DetokenizeError::Disabled)?;
+        if !expected.verify(access_key) {
+            return Err(DetokenizeError::Unauthorized);
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        if !entries.contains_key(token) {
+            // Possibly issued by another process sharing the vault file
+            match read_entries(&self.path) {
+                Ok(mut stored) => entries.append(&mut stored),
+                Err(e) => tracing::error!("failed to reload token vault: {:#}", e),
+            }
+        }
+        let entry = entries.get(token).ok_or_else(|| DetokenizeError::UnknownToken(token.to_string()))?;
+        let corrupt = || DetokenizeError::Corrupt(token.to_string());
+
+        let nonce = hex::decode(&entry.nonce).ok().filter(|n| n.len() == 12).ok_or_else(corrupt)?;
+        let ciphertext = hex::decode(&entry.ciphertext).map_err(|_| corrupt())?;
+        let plaintext = self.cipher
+            .decrypt(Nonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: token.as_bytes() })
+            .map_err(|_| corrupt())?;
+
+        String::from_utf8(plaintext).map_err(|_| corrupt())
+    }
+
+    fn token_for(&self, pii_type: &PiiType, value: &str) -> String {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.token_key).expect("HMAC accepts any key length");
+        mac.update(format!("{:?}", pii_type).as_bytes());
+        mac.update(&[0]);
+        mac.update(value.as_bytes());
+        let digest = hex::encode(mac.finalize().into_bytes());
+
+        format!("tok_{}_{}", token_prefix(pii_type), &digest[..TOKEN_DIGEST_CHARS])
+    }
+
+    /// Callers hold the file lock and have merged the stored entries
+    fn save(&self, entries: &BTreeMap<String, VaultEntry>) -> Result<()> {
+        let file = VaultFile { format_version: VAULT_FORMAT_VERSION, entries: entries.clone() };
+        files::write_atomic(&self.path, &serde_json::to_string_pretty(&file)?)
+    }
+}
+
+/// Entries stored at `path`, none if the vault has not been written yet
+fn read_entries(path: &Path) -> Result<BTreeMap<String, VaultEntry>> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read token vault {}", path.display()))?;
+    let file: VaultFile = serde_json::from_str(&contents)
+        .with_context(|| format!("{} is not a token vault", path.display()))?;
+    if file.format_version != VAULT_FORMAT_VERSION {
+        anyhow::bail!(
+            "token vault {} has format version {}, expected {}",
+            path.display(), file.format_version, VAULT_FORMAT_VERSION
+        );
+    }
+    Ok(file.entries)
+}
+
+fn token_prefix(pii_type: &PiiType) -> &'static str {
+    match pii_type {
+        PiiType::Email => "email",
+        PiiType::PhoneNumber => "phone",
+        PiiType::SocialSecurityNumber => "ssn",
+        PiiType::IndividualTaxpayerNumber => "itin",
+        PiiType::EmployerIdentificationNumber => "ein",
+        PiiType::CreditCardNumber => "card",
+        PiiType::IpAddress => "ip",
+        PiiType::DateOfBirth => "dob",
+        PiiType::Address => "address",
+        PiiType::Name => "name",
+        PiiType::Unknown => "pii",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vaults_sharing_a_file_keep_each_others_tokens() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.json");
+        let path = path.to_str().unwrap();
+        let key = [7u8; 32];
+
+        let first = TokenVault::open(path, key).unwrap().with_access_key("secret");
+        let second = TokenVault::open(path, key).unwrap().with_access_key("secret");
+        let a = first.tokenize(&PiiType::Email, "a@example.com").unwrap();
+        let b = second.tokenize(&PiiType::Email, "b@example.com").unwrap();
+
+        assert_eq!(first.detokenize(&b, "secret").unwrap(), "b@example.com");
+        assert_eq!(second.detokenize(&a, "secret").unwrap(), "a@example.com");
+
+        let reopened = TokenVault::open(path, key).unwrap().with_access_key("secret");
+        assert_eq!(reopened.detokenize(&a, "secret").unwrap(), "a@example.com");
+        assert_eq!(reopened.detokenize(&b, "secret").unwrap(), "b@example.com");
+    }
+}
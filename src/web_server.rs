@@ -1,11 +1,61 @@
-use actix_web::{web, App, HttpServer, HttpResponse};
+use actix_web::{web, App, HttpRequest, HttpServer, HttpResponse};
 use actix_cors::Cors;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
 use crate::{
+    types::{CardNetwork, PiiDetection, PiiType, RedactionSpan, Verification},
     coordinator::AgentCoordinator,
-    agents::{pii_scanner::PiiScannerAgent, compliance_enforcer::ComplianceEnforcerAgent, llm_reasoner::LlmReasonerAgent},
+    models::PiiClassifier,
+    policy::CompliancePolicy,
+    llm::LlmSetup,
+    audit::AuditLog,
+    access::AccessKey,
+    consent::{ConsentGrant, ConsentRegistry, CONSENT_ACCESS_KEY_ENV, DEFAULT_CONSENT_PURPOSE},
+    pseudonym::Pseudonymizer,
+    hashing::KeyedHasher,
+    synthetic::Synthesizer,
+    vault::{DetokenizeError, TokenVault},
+    agents::{LlmExtractorAgent, LlmVerifierAgent, pii_scanner::PiiScannerAgent, compliance_enforcer::ComplianceEnforcerAgent, llm_reasoner::LlmReasonerAgent},
 };
 
+/// Files loaded once when the server starts
+#[derive(Debug, Clone, Default)]
+pub struct ServerConfig {
+    /// Trained model; defaults to models/pii_classifier.json if present
+    pub model_path: Option<String>,
+    /// Redaction policy; defaults to config/policy.toml if present
+    pub policy_path: Option<String>,
+    /// Token vault; defaults to data/token_vault.json, keyed by PII_VAULT_KEY
+    pub vault_path: Option<String>,
+    /// Session and tenant pseudonym numbering; defaults to data/pseudonyms.json
+    pub pseudonym_path: Option<String>,
+    /// OpenAI-compatible LLM settings; defaults to config/llm.toml if present
+    pub llm_config_path: Option<String>,
+    /// Consent registry; defaults to data/consent.json
+    pub consent_path: Option<String>,
+    /// Audit log of scan decisions, keyed by PII_AUDIT_KEY; defaults to data/audit.jsonl when only the key is set
+    pub audit_path: Option<String>,
+}
+
+/// Vault shared by the scan and detokenize handlers, if one is configured
+pub type SharedVault = Option<Arc<TokenVault>>;
+
+/// Shared so session and tenant numbering stays consistent across requests
+pub type SharedPseudonymizer = Option<Arc<Pseudonymizer>>;
+
+/// Keys the `hash` action for every request, if `PII_HASH_SECRET` is set
+pub type SharedHasher = Option<Arc<KeyedHasher>>;
+
+/// Keys the `synthesize` action for every request, if `PII_SYNTHETIC_KEY` is set
+pub type SharedSynthesizer = Option<Arc<Synthesizer>>;
+
+/// Key the consent endpoints require; `None` disables them
+pub type ConsentAccess = Option<AccessKey>;
+
+/// Audit log every scan is recorded in, if one is configured
+pub type SharedAudit = Option<Arc<AuditLog>>;
+
 #[derive(Deserialize)]
 pub struct ScanRequest {
     pub text: String,
@@ -17,8 +67,55 @@ pub struct ScanResponse {
     pub pii_detected: Vec<PiiDetectionResponse>,
     pub compliance_score: f32,
     pub redacted_text: String,
+    pub redaction_map: Vec<RedactionSpan>,
     pub recommendations: Vec<String>,
     pub processing_time: u64,
+    /// Detections the LLM verifier judged not to be PII
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dismissed_pii: Vec<PiiDetectionResponse>,
+}
+
+#[derive(Deserialize)]
+pub struct ConsentGrantRequest {
+    pub user_id: String,
+    pub categories: Vec<PiiType>,
+    #[serde(default = "default_consent_purpose")]
+    pub purpose: String,
+    #[serde(default)]
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+fn default_consent_purpose() -> String {
+    DEFAULT_CONSENT_PURPOSE.to_string()
+}
+
+#[derive(Deserialize)]
+pub struct ConsentRevokeRequest {
+    pub user_id: String,
+    /// Revokes every purpose when omitted
+    #[serde(default)]
+    pub purpose: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ConsentRevokeResponse {
+    pub revoked: usize,
+}
+
+#[derive(Serialize)]
+pub struct ConsentListResponse {
+    pub user_id: String,
+    pub grants: Vec<ConsentGrant>,
+}
+
+#[derive(Deserialize)]
+pub struct DetokenizeRequest {
+    pub tokens: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct DetokenizeResponse {
+    pub values: BTreeMap<String, String>,
 }
 
 #[derive(Serialize)]
@@ -27,6 +124,38 @@ pub struct PiiDetectionResponse {
     pub value: String,
     pub confidence: f32,
     pub position: PositionResponse,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub card_network: Option<CardNetwork>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verification: Option<Verification>,
+}
+
+impl From<PiiDetection> for PiiDetectionResponse {
+    fn from(pii: PiiDetection) -> Self {
+        Self {
+            type_: match pii.pii_type {
+                crate::types::PiiType::Email => "email".to_string(),
+                crate::types::PiiType::PhoneNumber => "phone".to_string(),
+                crate::types::PiiType::SocialSecurityNumber => "ssn".to_string(),
+                crate::types::PiiType::IndividualTaxpayerNumber => "itin".to_string(),
+                crate::types::PiiType::EmployerIdentificationNumber => "ein".to_string(),
+                crate::types::PiiType::CreditCardNumber => "credit_card".to_string(),
+                crate::types::PiiType::IpAddress => "ip_address".to_string(),
+                crate::types::PiiType::DateOfBirth => "dob".to_string(),
+                crate::types::PiiType::Address => "address".to_string(),
+                crate::types::PiiType::Name => "name".to_string(),
+                crate::types::PiiType::Unknown => "unknown".to_string(),
+            },
+            value: pii.value,
+            confidence: pii.confidence,
+            position: PositionResponse {
+                start: pii.start_pos,
+                end: pii.end_pos,
+            },
+            card_network: pii.card_network,
+            verification: pii.verification,
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -35,16 +164,36 @@ pub struct PositionResponse {
     pub end: usize,
 }
 
-pub async fn scan_text(req: web::Json<ScanRequest>) -> Result<HttpResponse, actix_web::Error> {
+// One extractor per shared resource is the actix idiom
+#[allow(clippy::too_many_arguments)]
+pub async fn scan_text(
+    req: web::Json<ScanRequest>,
+    classifier: web::Data<PiiClassifier>,
+    policy: web::Data<CompliancePolicy>,
+    vault: web::Data<SharedVault>,
+    pseudonymizer: web::Data<SharedPseudonymizer>,
+    hasher: web::Data<SharedHasher>,
+    synthesizer: web::Data<SharedSynthesizer>,
+    llm: web::Data<LlmSetup>,
+    audit: web::Data<SharedAudit>,
+) -> Result<HttpResponse, actix_web::Error> {
     let start_time = std::time::Instant::now();
     
     println!("🔍 Received scan request for text: {}", req.text);
     
     // Initialize coordinator and agents
-    let coordinator = AgentCoordinator::new();
-    let scanner = PiiScannerAgent::new();
-    let enforcer = ComplianceEnforcerAgent::new();
-    let reasoner = LlmReasonerAgent::new();
+    let coordinator = AgentCoordinator::new()
+        .with_extractor(LlmExtractorAgent::from_setup(&llm))
+        .with_verifier(LlmVerifierAgent::from_setup(&llm))
+        .with_audit(audit.get_ref().clone());
+    let scanner = PiiScannerAgent::with_classifier(classifier.get_ref().clone());
+    let enforcer = ComplianceEnforcerAgent::new()
+        .with_policy(policy.into_inner())
+        .with_vault(vault.get_ref().clone())
+        .with_pseudonymizer(pseudonymizer.get_ref().clone())
+        .with_hasher(hasher.get_ref().clone())
+        .with_synthesizer(synthesizer.get_ref().clone());
+    let reasoner = LlmReasonerAgent::new().with_backend(llm.backend.clone(), llm.prompts.clone());
     
     // Run the compliance pipeline
     println!("🔄 Starting compliance pipeline...");
@@ -60,33 +209,17 @@ pub async fn scan_text(req: web::Json<ScanRequest>) -> Result<HttpResponse, acti
     println!("✅ Scan completed in {}ms", processing_time);
     
     // Convert to response format
-    let pii_detected = result.detected_pii.into_iter().map(|pii| PiiDetectionResponse {
-        type_: match pii.pii_type {
-            crate::types::PiiType::Email => "email".to_string(),
-            crate::types::PiiType::PhoneNumber => "phone".to_string(),
-            crate::types::PiiType::SocialSecurityNumber => "ssn".to_string(),
-            crate::types::PiiType::CreditCardNumber => "credit_card".to_string(),
-            crate::types::PiiType::IpAddress => "ip_address".to_string(),
-            crate::types::PiiType::DateOfBirth => "dob".to_string(),
-            crate::types::PiiType::Address => "address".to_string(),
-            crate::types::PiiType::Name => "name".to_string(),
-            crate::types::PiiType::Unknown => "unknown".to_string(),
-        },
-        value: pii.value,
-        confidence: pii.confidence,
-        position: PositionResponse {
-            start: pii.start_pos,
-            end: pii.end_pos,
-        },
-    }).collect();
+    let pii_detected = result.detected_pii.into_iter().map(PiiDetectionResponse::from).collect();
     
     let response = ScanResponse {
         text: result.original_text,
         pii_detected,
         compliance_score: result.compliance_score,
         redacted_text: result.redacted_text,
+        redaction_map: result.redaction_map,
         recommendations: result.recommendations,
         processing_time,
+        dismissed_pii: result.dismissed_pii.into_iter().map(PiiDetectionResponse::from).collect(),
     };
     
     println!("📊 Found {} PII items, compliance score: {:.1}%", 
@@ -95,13 +228,103 @@ pub async fn scan_text(req: web::Json<ScanRequest>) -> Result<HttpResponse, acti
     Ok(HttpResponse::Ok().json(response))
 }
 
+/// Resolves vault tokens for callers presenting `Authorization: Bearer <access key>`.
+/// Every token must resolve, otherwise nothing is returned.
+pub async fn detokenize(
+    http_req: HttpRequest,
+    req: web::Json<DetokenizeRequest>,
+    vault: web::Data<SharedVault>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(vault) = vault.get_ref() else {
+        return Err(actix_web::error::ErrorServiceUnavailable("no token vault configured"));
+    };
+    let access_key = bearer_token(&http_req);
+    
+    let mut values = BTreeMap::new();
+    for token in &req.tokens {
+        let value = vault.detokenize(token, access_key).map_err(|e| match e {
+            DetokenizeError::Disabled => actix_web::error::ErrorForbidden(e.to_string()),
+            DetokenizeError::Unauthorized => actix_web::error::ErrorUnauthorized(e.to_string()),
+            DetokenizeError::UnknownToken(_) => actix_web::error::ErrorNotFound(e.to_string()),
+            DetokenizeError::Corrupt(_) => actix_web::error::ErrorInternalServerError(e.to_string()),
+        })?;
+        values.insert(token.clone(), value);
+    }
+    
+    println!("🔓 Detokenized {} values", values.len());
+    Ok(HttpResponse::Ok().json(DetokenizeResponse { values }))
+}
+
+/// The key presented as `Authorization: Bearer <key>`, empty without one
+fn bearer_token(http_req: &HttpRequest) -> &str {
+    http_req.headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .unwrap_or("")
+}
+
+/// Consent endpoints, like detokenization, need `Authorization: Bearer <access key>`
+fn authorize_consent(http_req: &HttpRequest, access: &ConsentAccess) -> Result<(), actix_web::Error> {
+    let Some(access) = access else {
+        return Err(actix_web::error::ErrorForbidden(format!(
+            "consent endpoints are disabled: {} is not set", CONSENT_ACCESS_KEY_ENV
+        )));
+    };
+    if !access.verify(bearer_token(http_req)) {
+        return Err(actix_web::error::ErrorUnauthorized("access key rejected"));
+    }
+    Ok(())
+}
+
+pub async fn grant_consent(
+    http_req: HttpRequest,
+    req: web::Json<ConsentGrantRequest>,
+    consent: web::Data<Arc<ConsentRegistry>>,
+    access: web::Data<ConsentAccess>,
+) -> Result<HttpResponse, actix_web::Error> {
+    authorize_consent(&http_req, &access)?;
+    let req = req.into_inner();
+    let grant = consent.grant(&req.user_id, req.categories, &req.purpose, req.expires_at)
+        .map_err(|e| actix_web::error::ErrorBadRequest(format!("{:#}", e)))?;
+    
+    println!("✅ Consent granted for {} ({})", grant.user_id, grant.purpose);
+    Ok(HttpResponse::Ok().json(grant))
+}
+
+pub async fn revoke_consent(
+    http_req: HttpRequest,
+    req: web::Json<ConsentRevokeRequest>,
+    consent: web::Data<Arc<ConsentRegistry>>,
+    access: web::Data<ConsentAccess>,
+) -> Result<HttpResponse, actix_web::Error> {
+    authorize_consent(&http_req, &access)?;
+    let revoked = consent.revoke(&req.user_id, req.purpose.as_deref())
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("{:#}", e)))?;
+    
+    println!("🚫 Revoked {} consent grant(s) for {}", revoked, req.user_id);
+    Ok(HttpResponse::Ok().json(ConsentRevokeResponse { revoked }))
+}
+
+pub async fn list_consent(
+    http_req: HttpRequest,
+    user_id: web::Path<String>,
+    consent: web::Data<Arc<ConsentRegistry>>,
+    access: web::Data<ConsentAccess>,
+) -> Result<HttpResponse, actix_web::Error> {
+    authorize_consent(&http_req, &access)?;
+    let user_id = user_id.into_inner();
+    let grants = consent.grants(&user_id);
+    Ok(HttpResponse::Ok().json(ConsentListResponse { user_id, grants }))
+}
+
 pub async fn test_endpoint() -> Result<HttpResponse, actix_web::Error> {
     println!("🧪 Test endpoint called");
     
     // Test basic agent creation
-    let scanner = PiiScannerAgent::new();
-    let enforcer = ComplianceEnforcerAgent::new();
-    let reasoner = LlmReasonerAgent::new();
+    let _scanner = PiiScannerAgent::new();
+    let _enforcer = ComplianceEnforcerAgent::new();
+    let _reasoner = LlmReasonerAgent::new();
     
     println!("✅ Agents created successfully");
     
@@ -121,14 +344,67 @@ pub async fn health_check() -> Result<HttpResponse, actix_web::Error> {
     })))
 }
 
-pub async fn start_web_server() -> std::io::Result<()> {
+pub async fn start_web_server(config: ServerConfig) -> std::io::Result<()> {
+    // Load the classifier and policy once at startup and share them across workers
+    let classifier = PiiClassifier::load_or_default(config.model_path.as_deref())
+        .map_err(|e| std::io::Error::other(format!("failed to load PII model: {:#}", e)))?;
+    match classifier.metadata().trained_at {
+        Some(trained_at) => println!("🧠 Loaded PII model trained at {}", trained_at),
+        None => println!("🧠 Using built-in PII patterns"),
+    }
+    let classifier = web::Data::new(classifier);
+    
+    let policy = CompliancePolicy::load_or_default(config.policy_path.as_deref())
+        .map_err(|e| std::io::Error::other(format!("failed to load policy: {:#}", e)))?;
+    println!("📜 Using redaction policy version {}", policy.version);
+    
+    let vault = TokenVault::for_policy(&policy, config.vault_path.as_deref())
+        .map_err(|e| std::io::Error::other(format!("failed to open token vault: {:#}", e)))?;
+    if let Some(vault) = &vault {
+        println!("🔐 Token vault holds {} values", vault.len());
+    }
+    let vault: web::Data<SharedVault> = web::Data::new(vault.map(Arc::new));
+    let pseudonymizer = Pseudonymizer::for_policy(&policy, config.pseudonym_path.as_deref())
+        .map_err(|e| std::io::Error::other(format!("failed to configure pseudonymization: {:#}", e)))?;
+    let pseudonymizer: web::Data<SharedPseudonymizer> = web::Data::new(pseudonymizer.map(Arc::new));
+    let hasher = KeyedHasher::for_policy(&policy)
+        .map_err(|e| std::io::Error::other(format!("failed to configure hashing: {:#}", e)))?;
+    let hasher: web::Data<SharedHasher> = web::Data::new(hasher.map(Arc::new));
+    let synthesizer = Synthesizer::for_policy(&policy)
+        .map_err(|e| std::io::Error::other(format!("failed to configure synthesis: {:#}", e)))?;
+    let synthesizer: web::Data<SharedSynthesizer> = web::Data::new(synthesizer.map(Arc::new));
+    
+    let llm = LlmSetup::load(config.llm_config_path.as_deref())
+        .map_err(|e| std::io::Error::other(format!("failed to configure LLM: {:#}", e)))?;
+    if let Some(backend) = &llm.backend {
+        println!("🤖 Using LLM model {}", backend.model());
+    }
+    let llm = web::Data::new(llm);
+    let policy = web::Data::new(policy);
+    
+    let consent = ConsentRegistry::open_or_default(config.consent_path.as_deref())
+        .map_err(|e| std::io::Error::other(format!("failed to open consent registry: {:#}", e)))?;
+    let consent = web::Data::new(Arc::new(consent));
+    let consent_access: web::Data<ConsentAccess> = web::Data::new(AccessKey::from_env(CONSENT_ACCESS_KEY_ENV));
+    if consent_access.is_none() {
+        println!("⚠️  {} is not set; consent endpoints are disabled", CONSENT_ACCESS_KEY_ENV);
+    }
+    
+    let audit = AuditLog::open_configured(config.audit_path.as_deref())
+        .map_err(|e| std::io::Error::other(format!("failed to open audit log: {:#}", e)))?;
+    let audit: web::Data<SharedAudit> = web::Data::new(audit.map(Arc::new));
+    
     println!("🚀 Starting PII Compliance Agent web server on http://localhost:8000");
     println!("📡 Available endpoints:");
     println!("   POST /api/scan - Scan text for PII");
+    println!("   POST /api/detokenize - Resolve vault tokens (Bearer access key)");
+    println!("   POST /api/consent/grant - Record a user's consent (Bearer access key)");
+    println!("   POST /api/consent/revoke - Revoke a user's consent (Bearer access key)");
+    println!("   GET  /api/consent/{{user_id}} - List a user's consent grants (Bearer access key)");
     println!("   GET  /health   - Health check");
     println!("   GET  /test     - Test endpoint");
     
-    HttpServer::new(|| {
+    HttpServer::new(move || {
         let cors = Cors::default()
             .allow_any_origin()
             .allow_any_method()
@@ -136,7 +412,21 @@ pub async fn start_web_server() -> std::io::Result<()> {
             
         App::new()
             .wrap(cors)
+            .app_data(classifier.clone())
+            .app_data(policy.clone())
+            .app_data(vault.clone())
+            .app_data(pseudonymizer.clone())
+            .app_data(hasher.clone())
+            .app_data(synthesizer.clone())
+            .app_data(llm.clone())
+            .app_data(consent.clone())
+            .app_data(consent_access.clone())
+            .app_data(audit.clone())
             .route("/api/scan", web::post().to(scan_text))
+            .route("/api/detokenize", web::post().to(detokenize))
+            .route("/api/consent/grant", web::post().to(grant_consent))
+            .route("/api/consent/revoke", web::post().to(revoke_consent))
+            .route("/api/consent/{user_id}", web::get().to(list_consent))
             .route("/health", web::get().to(health_check))
             .route("/test", web::get().to(test_endpoint))
     })